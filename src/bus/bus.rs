@@ -1,22 +1,56 @@
+use std::io;
+
 use crate::joypad::joypad::{JOYPAD_REGISTER, Joypad};
-use crate::mappers::mapper::Mapper;
-use crate::mappers::no_mbc::NoMbc;
+use crate::mappers::camera::CameraSensor;
+use crate::mappers::infrared::InfraredTransport;
+use crate::mappers::mapper::{self, Mapper};
+use crate::mappers::rtc::RtcMode;
+use crate::mappers::speaker::TonePlayer;
+use crate::model::Model;
+use crate::rom::boot_rom::{BOOT_ROM_SIZE, BootRom};
 use crate::rom::cartridge::Cartridge;
+use crate::rom::rom_header::CgbFlag;
+use crate::utils::test_bit;
 
 const DMA_REGISTER: u16 = 0xFF46;
+
+// CGB registers, they only exist in CGB mode
+// Bit 0 prepares a speed switch, bit 7 is the current speed
+const KEY1_REGISTER: usize = 0xFF4D;
+const VBK_REGISTER: usize = 0xFF4F;
+const SVBK_REGISTER: usize = 0xFF70;
+// Undocumented registers with no known use, 0xFF74 is only there in CGB mode
+const UNDOCUMENTED_REGISTERS_START: usize = 0xFF72;
+const UNDOCUMENTED_REGISTERS_END: usize = 0xFF75;
+
+const VRAM_BANK_SIZE: usize = 0x2000;
+const WRAM_BANK_SIZE: usize = 0x1000;
+// Writing a non-zero value unmaps the boot ROM until the next power cycle
+const BOOT_ROM_DISABLE_REGISTER: u16 = 0xFF50;
 const OAM_START: u16 = 0xFE00;
 
 #[derive(Debug)]
 pub struct Bus {
     // 16KiB ROM bank 00
     // 16 KiB from cartridge, switchable banks
-    mapper: NoMbc,
-    // 8KiB Video RAM
-    vram: [u8; 0x2000],
-    // 8KiB External RAM
-    ram: [u8; 0x2000],
-    // 8KiB Work RAM
-    wram: [u8; 0x2000],
+    // External RAM also lives on the cartridge, so it is handled by the mapper
+    mapper: Box<dyn Mapper>,
+    model: Model,
+    // Mapped over the start of the cartridge ROM while it runs
+    boot_rom: Option<BootRom>,
+    // 8KiB Video RAM, the CGB has 2 banks
+    vram: [u8; VRAM_BANK_SIZE * 2],
+    // 8KiB Work RAM, the CGB has 8 4KiB banks with bank 0 always at 0xC000
+    wram: [u8; WRAM_BANK_SIZE * 8],
+    // A CGB running a CGB cartridge, the CGB registers are locked when it runs a DMG cartridge
+    is_cgb_mode: bool,
+    // VBK, the VRAM bank mapped at 0x8000
+    vram_bank: u8,
+    // SVBK, the WRAM bank mapped at 0xD000. 0 maps bank 1.
+    wram_bank: u8,
+    is_double_speed: bool,
+    is_speed_switch_armed: bool,
+    undocumented_registers: [u8; 4],
 
     // 160 bytes Sprite Attribute Table
     oam: [u8; 0xA0],
@@ -37,32 +71,53 @@ pub struct Bus {
 }
 
 impl Bus {
-    pub fn new(cartridge: &Cartridge) -> Self {
-        Bus {
-            mapper: NoMbc::new(cartridge),
-            vram: [0; 0x2000],
-            ram: [0; 0x2000],
-            wram: [0; 0x2000],
+    pub fn new(cartridge: &Cartridge, boot_rom: Option<BootRom>, model: Model) -> io::Result<Self> {
+        Ok(Bus {
+            mapper: mapper::get_mapper(cartridge)?,
+            model,
+            is_cgb_mode: model.is_cgb() && cartridge.rom_header.cgb_flag != CgbFlag::DmgOnly,
+            boot_rom,
+            vram: [0; VRAM_BANK_SIZE * 2],
+            wram: [0; WRAM_BANK_SIZE * 8],
+            vram_bank: 0,
+            wram_bank: 0,
+            is_double_speed: false,
+            is_speed_switch_armed: false,
+            undocumented_registers: [0; 4],
             oam: [0; 0xA0],
             io_regs: [0; 0x80],
             hram: [0; 0x7F],
             ie_reg: 0,
             temp: 0,
             joypad: Joypad::new(),
-        }
+        })
     }
 
     pub fn read_byte(&self, addr: u16) -> u8 {
         let index = addr as usize;
+        if let Some(boot_rom) = &self.boot_rom
+            && index < BOOT_ROM_SIZE
+        {
+            return boot_rom.read(addr);
+        }
+
         match index {
             0x0000..0x8000 => self.mapper.read(addr),
-            0x8000..=0x9FFF => self.vram[index - 0x8000],
-            0xA000..=0xBFFF => self.ram[index - 0xA000],
-            0xC000..=0xDFFF => self.wram[index - 0xC000],
+            0x8000..=0x9FFF => self.vram[self.get_vram_index(index)],
+            0xA000..=0xBFFF => self.mapper.read(addr),
+            0xC000..=0xDFFF => self.wram[self.get_wram_index(index)],
             0xE000..=0xFDFF => unimplemented!("Echo RAM is not implemented"),
             0xFE00..=0xFE9F => self.oam[index - 0xFE00],
             0xFEA0..=0xFEFF => 0xFF, // Non usable memory area, when read, returns 0xFF
             JOYPAD_REGISTER => self.joypad.read(),
+            KEY1_REGISTER if self.is_cgb_mode => {
+                0x7E | (self.is_double_speed as u8) << 7 | self.is_speed_switch_armed as u8
+            }
+            VBK_REGISTER if self.is_cgb_mode => 0xFE | self.vram_bank,
+            SVBK_REGISTER if self.is_cgb_mode => 0xF8 | self.wram_bank,
+            UNDOCUMENTED_REGISTERS_START..=UNDOCUMENTED_REGISTERS_END if self.model.is_cgb() => {
+                self.read_undocumented_register(index)
+            }
             0xFF00..=0xFF7F => self.io_regs[index - 0xFF00],
             0xFF80..=0xFFFE => self.hram[index - 0xFF80],
             0xFFFF => self.ie_reg,
@@ -82,9 +137,9 @@ impl Bus {
 
         match index {
             0x0000..=0x7FFF => self.mapper.write(addr, value),
-            0x8000..=0x9FFF => self.vram[index - 0x8000] = value,
-            0xA000..=0xBFFF => self.ram[index - 0xA000] = value,
-            0xC000..=0xDFFF => self.wram[index - 0xC000] = value,
+            0x8000..=0x9FFF => self.vram[self.get_vram_index(index)] = value,
+            0xA000..=0xBFFF => self.mapper.write(addr, value),
+            0xC000..=0xDFFF => self.wram[self.get_wram_index(index)] = value,
             0xE000..=0xFDFF => {
                 // Echo RAM, typically mirrors C000-DDFF
                 unimplemented!("Echo RAM is not implemented");
@@ -100,10 +155,19 @@ impl Bus {
             JOYPAD_REGISTER => {
                 self.joypad.write(value);
             }
+            KEY1_REGISTER if self.is_cgb_mode => self.is_speed_switch_armed = test_bit(value, 0),
+            VBK_REGISTER if self.is_cgb_mode => self.vram_bank = value & 0x01,
+            SVBK_REGISTER if self.is_cgb_mode => self.wram_bank = value & 0x07,
+            UNDOCUMENTED_REGISTERS_START..=UNDOCUMENTED_REGISTERS_END if self.model.is_cgb() => {
+                self.undocumented_registers[index - UNDOCUMENTED_REGISTERS_START] = value;
+            }
             0xFF00..=0xFF7F => {
                 if addr == DMA_REGISTER {
                     self.do_dma_transfer(value);
                 }
+                if addr == BOOT_ROM_DISABLE_REGISTER && value != 0 {
+                    self.boot_rom = None;
+                }
                 // TODO: Need to handle changes in the clock frequency
                 self.io_regs[index - 0xFF00] = value;
             }
@@ -126,15 +190,20 @@ impl Bus {
     pub fn get_pointer(&mut self, addr: u16) -> &mut u8 {
         let index = addr as usize;
         match index {
-            0x0000..=0x7FFF => {
-                // ROM is read-only, cannot return a mutable pointer
+            0x0000..=0x7FFF | 0xA000..=0xBFFF => {
+                // ROM is read-only and external RAM is owned by the mapper, cannot return a mutable pointer
                 // fake returning a mutable pointer
                 self.temp = self.mapper.read(addr);
                 return &mut self.temp;
             }
-            0x8000..=0x9FFF => &mut self.vram[index - 0x8000],
-            0xA000..=0xBFFF => &mut self.ram[index - 0xA000],
-            0xC000..=0xDFFF => &mut self.wram[index - 0xC000],
+            0x8000..=0x9FFF => {
+                let vram_index = self.get_vram_index(index);
+                &mut self.vram[vram_index]
+            }
+            0xC000..=0xDFFF => {
+                let wram_index = self.get_wram_index(index);
+                &mut self.wram[wram_index]
+            }
             0xE000..=0xFDFF => panic!("Cannot get mutable pointer to Echo RAM"),
             0xFE00..=0xFE9F => &mut self.oam[index - 0xFE00],
             0xFEA0..=0xFEFF => panic!("Cannot get mutable pointer to Not usable memory area"),
@@ -145,6 +214,108 @@ impl Bus {
         }
     }
 
+    pub fn get_model(&self) -> Model {
+        self.model
+    }
+
+    /**
+     * CGB features are only there for CGB games on a CGB, DMG games run in compatibility mode
+     */
+    pub fn is_cgb_mode(&self) -> bool {
+        self.is_cgb_mode
+    }
+
+    /**
+     * Read from a VRAM bank, no matter which bank VBK maps
+     */
+    pub fn read_vram(&self, addr: u16, bank: usize) -> u8 {
+        self.vram[bank * VRAM_BANK_SIZE + addr as usize - 0x8000]
+    }
+
+    pub fn is_double_speed(&self) -> bool {
+        self.is_double_speed
+    }
+
+    /**
+     * Returns whether STOP should switch speed, a game arms the switch by setting bit 0 of KEY1
+     */
+    pub fn is_speed_switch_armed(&self) -> bool {
+        self.is_speed_switch_armed
+    }
+
+    pub fn switch_speed(&mut self) {
+        self.is_double_speed = !self.is_double_speed;
+        self.is_speed_switch_armed = false;
+    }
+
+    fn get_vram_index(&self, index: usize) -> usize {
+        self.vram_bank as usize * VRAM_BANK_SIZE + index - 0x8000
+    }
+
+    fn get_wram_index(&self, index: usize) -> usize {
+        match index {
+            0xC000..=0xCFFF => index - 0xC000,
+            _ => self.wram_bank.max(1) as usize * WRAM_BANK_SIZE + index - 0xD000,
+        }
+    }
+
+    fn read_undocumented_register(&self, index: usize) -> u8 {
+        let value = self.undocumented_registers[index - UNDOCUMENTED_REGISTERS_START];
+        match index {
+            0xFF74 if !self.is_cgb_mode => 0xFF,
+            // Only bits 4-6 can be written, the others read as 1
+            UNDOCUMENTED_REGISTERS_END => 0x8F | (value & 0x70),
+            _ => value,
+        }
+    }
+
+    pub fn get_save_data(&self) -> Vec<u8> {
+        self.mapper.get_save_data()
+    }
+
+    pub fn load_save_data(&mut self, data: &[u8]) {
+        self.mapper.load_save_data(data);
+    }
+
+    /**
+     * Returns whether battery backed memory on the cartridge was written to since the last call
+     */
+    pub fn take_ram_written(&mut self) -> bool {
+        self.mapper.take_ram_written()
+    }
+
+    pub fn update_mapper(&mut self, cycles: usize) {
+        self.mapper.step(cycles);
+    }
+
+    pub fn skip_boot_rom(&mut self) {
+        self.mapper.skip_boot();
+    }
+
+    pub fn set_rtc_mode(&mut self, mode: RtcMode) {
+        self.mapper.set_rtc_mode(mode);
+    }
+
+    pub fn is_rumbling(&self) -> bool {
+        self.mapper.is_rumbling()
+    }
+
+    pub fn set_tilt(&mut self, x: f32, y: f32) {
+        self.mapper.set_tilt(x, y);
+    }
+
+    pub fn set_camera_sensor(&mut self, sensor: Box<dyn CameraSensor>) {
+        self.mapper.set_camera_sensor(sensor);
+    }
+
+    pub fn set_infrared_transport(&mut self, transport: Box<dyn InfraredTransport>) {
+        self.mapper.set_infrared_transport(transport);
+    }
+
+    pub fn set_tone_player(&mut self, player: Box<dyn TonePlayer>) {
+        self.mapper.set_tone_player(player);
+    }
+
     fn do_dma_transfer(&mut self, value: u8) {
         // Value is the source address divided by 100
         let addr = (value as u16) << 8;
@@ -6,6 +6,7 @@ use crate::{
         bus::Bus,
         interrupt_flags::{self, InterruptType},
     },
+    model::Model,
     utils::{self},
 };
 
@@ -30,6 +31,23 @@ impl Timer {
         }
     }
 
+    /**
+     * The timer as the boot ROM leaves it. DIV is the top byte of a 16 bit counter, the DMG and MGB
+     * boot ROMs leave it at 0xABCC. The other boot ROMs take a different time depending on the
+     * cartridge, so their phase isn't known and it starts at 0 like DIV does.
+     */
+    pub fn after_boot(model: Model) -> Self {
+        let divider_counter = match model {
+            Model::Dmg | Model::Mgb => 0xCC,
+            Model::Dmg0 | Model::Sgb | Model::Sgb2 | Model::Cgb | Model::Agb => 0,
+        };
+
+        Self {
+            divider_counter,
+            ..Self::new()
+        }
+    }
+
     pub fn update_timer(&mut self, bus: &mut Bus, mut cycles: usize) {
         cycles = cycles * 4;
         let cycles = cycles.try_into().unwrap();
@@ -97,3 +115,43 @@ impl Timer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::rom::cartridge::Cartridge;
+
+    fn get_bus(model: Model) -> Bus {
+        let cartridge =
+            Cartridge::from_data("timer.gb".to_string(), vec![0; 0x8000], &Config::default())
+                .unwrap();
+        Bus::new(&cartridge, None, model).unwrap()
+    }
+
+    // M-cycles until DIV first ticks over
+    fn get_cycles_to_div_tick(model: Model) -> usize {
+        let mut bus = get_bus(model);
+        let mut timer = Timer::after_boot(model);
+        let div = bus.read_byte(DIVIDER_REGISTER);
+
+        let mut cycles = 0;
+        while bus.read_byte(DIVIDER_REGISTER) == div {
+            timer.update_timer(&mut bus, 1);
+            cycles += 1;
+        }
+        cycles
+    }
+
+    #[test]
+    fn dmg_boot_leaves_div_mid_count() {
+        // 0xCC of the 0x100 T-cycles have passed
+        assert_eq!(get_cycles_to_div_tick(Model::Dmg), (0x100 - 0xCC) / 4);
+        assert_eq!(get_cycles_to_div_tick(Model::Mgb), (0x100 - 0xCC) / 4);
+    }
+
+    #[test]
+    fn unknown_phase_starts_at_zero() {
+        assert_eq!(get_cycles_to_div_tick(Model::Cgb), 0x100 / 4);
+    }
+}
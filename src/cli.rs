@@ -0,0 +1,385 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::process::ExitCode;
+
+use crate::config::Config;
+use crate::emu;
+use crate::mappers::camera::ImageFileSensor;
+use crate::mappers::rtc::RtcMode;
+use crate::model::Model;
+use crate::rom::boot_rom::BootRom;
+use crate::rom::cartridge::{Cartridge, LoadOptions};
+use crate::rom::catalog;
+use crate::rom::database::{GameDatabase, RomHashes};
+use crate::rom::fixer::{self, HeaderFix};
+use crate::rom::info::RomInfo;
+use crate::rom::rom_header::CgbFlag;
+use crate::rom::validation::LockUpPolicy;
+use crate::ui;
+
+const USAGE: &str = "Usage:
+    gameboy-emulator [run] <rom> [--entry <name>] [--patch <path>] [--host-rtc]
+        [--camera-image <path>] [--boot-check <ignore|refuse|lock-up>] [--boot-rom <path>]
+        [--model <dmg0|dmg|mgb|sgb|sgb2|cgb|agb>] [--config <path>]
+    gameboy-emulator info <rom> [--entry <name>] [--patch <path>] [--json]
+    gameboy-emulator verify <rom> [--entry <name>] [--patch <path>] [--json]
+    gameboy-emulator scan <dir> [--format <json|csv>] [--dat <path>] [--output <path>]
+        [--config <path>]
+    gameboy-emulator fix <rom> [--output <path>] [--title <title>] [--cgb <compatible|only>] [--sgb]
+        [--licensee <code>] [--old-licensee <byte>] [--mapper <byte>] [--ram-size <byte>]";
+
+// The exit code tells scripts whether the command found a problem
+type CliResult = Result<ExitCode, Box<dyn Error>>;
+
+/**
+ * Parse the command line and run the subcommand, a ROM path on its own runs the game
+ */
+pub fn run(args: impl Iterator<Item = String>) -> CliResult {
+    let mut args = args.peekable();
+
+    match args.peek().map(String::as_str) {
+        Some("run") => {
+            args.next();
+            run_game(args)
+        }
+        Some("info") => {
+            args.next();
+            print_info(args)
+        }
+        Some("verify") => {
+            args.next();
+            verify(args)
+        }
+        Some("scan") => {
+            args.next();
+            scan(args)
+        }
+        Some("fix") => {
+            args.next();
+            fix(args)
+        }
+        Some("help" | "--help" | "-h") => {
+            println!("{}", USAGE);
+            Ok(ExitCode::SUCCESS)
+        }
+        Some(_) => run_game(args),
+        None => Err(usage_error("Missing ROM path")),
+    }
+}
+
+fn run_game(mut args: impl Iterator<Item = String>) -> CliResult {
+    let mut file_path = None;
+    let mut use_host_rtc = false;
+    let mut camera_image = None;
+    let mut lock_up_policy = LockUpPolicy::Ignore;
+    let mut load_options = LoadOptions::default();
+    let mut config_path = None;
+    let mut boot_rom_path = None;
+    let mut model = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--entry" => load_options.archive_entry = Some(get_value(&mut args, "--entry")?),
+            "--patch" => load_options.patch = Some(get_value(&mut args, "--patch")?),
+            "--config" => config_path = Some(get_value(&mut args, "--config")?),
+            "--boot-rom" => boot_rom_path = Some(get_value(&mut args, "--boot-rom")?),
+            "--model" => {
+                let value = get_value(&mut args, "--model")?;
+                model = Some(value.parse::<Model>().map_err(|error| {
+                    usage_error(&format!("Invalid --model {}: {}", value, error))
+                })?);
+            }
+            "--host-rtc" => use_host_rtc = true,
+            "--camera-image" => {
+                camera_image = Some(get_value(&mut args, "--camera-image")?);
+            }
+            "--boot-check" => {
+                lock_up_policy = match get_value(&mut args, "--boot-check")?.as_str() {
+                    "ignore" => LockUpPolicy::Ignore,
+                    "refuse" => LockUpPolicy::Refuse,
+                    "lock-up" => LockUpPolicy::Emulate,
+                    _ => {
+                        return Err(usage_error(
+                            "--boot-check expects ignore, refuse or lock-up",
+                        ));
+                    }
+                };
+            }
+            _ => set_file_path(&mut file_path, arg)?,
+        }
+    }
+
+    let file_path = file_path.ok_or_else(|| usage_error("Missing ROM path"))?;
+    load_options.config = Config::load(config_path.as_deref())?;
+    let cartridge = Cartridge::new(&file_path, &load_options)?;
+    let key_bindings = cartridge.get_config().get_key_bindings()?;
+    let boot_rom = match boot_rom_path.or_else(|| cartridge.get_config().boot_rom.clone()) {
+        Some(boot_rom_path) => Some(BootRom::load(&boot_rom_path)?),
+        None => None,
+    };
+
+    // Show the real name of the game if it is in the DAT, header titles are cut short
+    let database = match load_options.config.get_dat_path() {
+        Some(dat_path) => GameDatabase::load(dat_path),
+        None => GameDatabase::embedded(),
+    };
+    let database = match database {
+        Ok(database) => Some(database),
+        Err(error) => {
+            eprintln!("Warning: {}, using the title from the header", error);
+            None
+        }
+    };
+    let title = match database
+        .as_ref()
+        .and_then(|database| database.find(&RomHashes::new(cartridge.get_data())))
+    {
+        Some(game) => game.name.clone(),
+        None => cartridge.rom_header.title.clone(),
+    };
+
+    let report = cartridge.validate();
+    if !report.is_valid() {
+        eprintln!("Warning: the ROM failed validation\n{}", report);
+    }
+    if report.would_lock_up() && lock_up_policy == LockUpPolicy::Refuse {
+        return Err("The boot ROM would refuse to start this cartridge".into());
+    }
+
+    // A real boot ROM locks up on its own
+    let should_lock_up =
+        boot_rom.is_none() && report.would_lock_up() && lock_up_policy == LockUpPolicy::Emulate;
+
+    // Options on the command line win over the config
+    let mut context = emu::Context::new(cartridge, boot_rom, model)?;
+    if should_lock_up {
+        context.lock_up();
+    }
+    if use_host_rtc {
+        context.set_rtc_mode(RtcMode::Host);
+    }
+    if let Some(camera_image) = camera_image {
+        context.set_camera_sensor(Box::new(ImageFileSensor::new(&camera_image)?));
+    }
+    let mut ui = ui::UI::new(context, &title, key_bindings);
+    ui.start();
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/**
+ * Print the parsed header
+ */
+fn print_info(args: impl Iterator<Item = String>) -> CliResult {
+    let (file_path, load_options, is_json) = parse_report_args(args)?;
+    let info = RomInfo::new(&Cartridge::new(&file_path, &load_options)?);
+
+    if is_json {
+        println!("{}", serde_json::to_string_pretty(&info)?);
+    } else {
+        println!("{}", info);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/**
+ * Print the validation report, fails if the ROM failed any check so scripts can catch bad builds
+ */
+fn verify(args: impl Iterator<Item = String>) -> CliResult {
+    let (file_path, load_options, is_json) = parse_report_args(args)?;
+    let report = Cartridge::new(&file_path, &load_options)?.validate();
+
+    if is_json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        println!("{}", report);
+    }
+
+    if !report.is_valid() {
+        return Ok(ExitCode::FAILURE);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/**
+ * Catalog every ROM in a folder, matched against a DAT file from the command line or the config,
+ * or the embedded one
+ */
+fn scan(mut args: impl Iterator<Item = String>) -> CliResult {
+    let mut dir = None;
+    let mut is_csv = false;
+    let mut dat_path = None;
+    let mut output_path = None;
+    let mut config_path = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => {
+                is_csv = match get_value(&mut args, "--format")?.as_str() {
+                    "json" => false,
+                    "csv" => true,
+                    _ => return Err(usage_error("--format expects json or csv")),
+                };
+            }
+            "--dat" => dat_path = Some(get_value(&mut args, "--dat")?),
+            "--output" => output_path = Some(get_value(&mut args, "--output")?),
+            "--config" => config_path = Some(get_value(&mut args, "--config")?),
+            _ => set_file_path(&mut dir, arg)?,
+        }
+    }
+
+    let dir = dir.ok_or_else(|| usage_error("Missing folder to scan"))?;
+    let config = Config::load(config_path.as_deref())?;
+    let database = match dat_path.as_deref().or(config.get_dat_path()) {
+        Some(dat_path) => GameDatabase::load(dat_path)?,
+        None => GameDatabase::embedded()?,
+    };
+
+    let entries = catalog::scan(Path::new(&dir), &database)?;
+    let output = if is_csv {
+        catalog::to_csv(&entries)
+    } else {
+        serde_json::to_string_pretty(&entries)? + "\n"
+    };
+
+    match output_path {
+        Some(output_path) => fs::write(output_path, output)?,
+        None => print!("{}", output),
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/**
+ * Fix the header of a ROM in place, or write the fixed ROM somewhere else
+ */
+fn fix(mut args: impl Iterator<Item = String>) -> CliResult {
+    let mut file_path = None;
+    let mut output_path = None;
+    let mut header_fix = HeaderFix::default();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--output" => output_path = Some(get_value(&mut args, "--output")?),
+            "--title" => header_fix.title = Some(get_value(&mut args, "--title")?),
+            "--cgb" => {
+                header_fix.cgb_flag = match get_value(&mut args, "--cgb")?.as_str() {
+                    "compatible" => Some(CgbFlag::Compatible),
+                    "only" => Some(CgbFlag::CgbOnly),
+                    _ => return Err(usage_error("--cgb expects compatible or only")),
+                };
+            }
+            "--sgb" => header_fix.is_sgb = true,
+            "--licensee" => {
+                header_fix.new_licensee_code = Some(get_value(&mut args, "--licensee")?);
+            }
+            "--old-licensee" => {
+                header_fix.old_licensee_code = Some(get_byte(&mut args, "--old-licensee")?);
+            }
+            "--mapper" => header_fix.cartridge_type = Some(get_byte(&mut args, "--mapper")?),
+            "--ram-size" => header_fix.ram_size = Some(get_byte(&mut args, "--ram-size")?),
+            _ => set_file_path(&mut file_path, arg)?,
+        }
+    }
+
+    let file_path = file_path.ok_or_else(|| usage_error("Missing ROM path"))?;
+    let mut rom = fs::read(&file_path)?;
+    fixer::fix_rom(&mut rom, &header_fix)?;
+    fs::write(output_path.unwrap_or(file_path), rom)?;
+    Ok(ExitCode::SUCCESS)
+}
+
+fn parse_report_args(
+    mut args: impl Iterator<Item = String>,
+) -> Result<(String, LoadOptions, bool), Box<dyn Error>> {
+    let mut file_path = None;
+    let mut load_options = LoadOptions::default();
+    let mut is_json = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--entry" => load_options.archive_entry = Some(get_value(&mut args, "--entry")?),
+            "--patch" => load_options.patch = Some(get_value(&mut args, "--patch")?),
+            "--json" => is_json = true,
+            _ => set_file_path(&mut file_path, arg)?,
+        }
+    }
+
+    let file_path = file_path.ok_or_else(|| usage_error("Missing ROM path"))?;
+    Ok((file_path, load_options, is_json))
+}
+
+fn set_file_path(file_path: &mut Option<String>, arg: String) -> Result<(), Box<dyn Error>> {
+    if arg.starts_with("--") {
+        return Err(usage_error(&format!("Unknown option {}", arg)));
+    }
+    if file_path.is_some() {
+        return Err(usage_error(&format!("Unexpected argument {}", arg)));
+    }
+
+    *file_path = Some(arg);
+    Ok(())
+}
+
+fn get_value(
+    args: &mut impl Iterator<Item = String>,
+    option: &str,
+) -> Result<String, Box<dyn Error>> {
+    args.next()
+        .ok_or_else(|| usage_error(&format!("Missing value for {}", option)))
+}
+
+/**
+ * Bytes can be given in decimal, or in hex with a 0x or $ prefix
+ */
+fn get_byte(args: &mut impl Iterator<Item = String>, option: &str) -> Result<u8, Box<dyn Error>> {
+    let value = get_value(args, option)?;
+    let byte = match value.strip_prefix("0x").or_else(|| value.strip_prefix('$')) {
+        Some(hex) => u8::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+
+    byte.map_err(|_| usage_error(&format!("{} expects a byte, got {}", option, value)))
+}
+
+fn usage_error(message: &str) -> Box<dyn Error> {
+    format!("{}\n\n{}", message, USAGE).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> impl Iterator<Item = String> {
+        args.iter()
+            .map(|arg| arg.to_string())
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn verify_fails_on_bad_rom() {
+        let path = std::env::temp_dir().join("cli_verify_bad_rom.gb");
+        fs::write(&path, vec![0; 0x8000]).unwrap();
+
+        let exit_code = run(args(&["verify", path.to_str().unwrap()])).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(exit_code, ExitCode::FAILURE);
+    }
+
+    #[test]
+    fn parses_bytes_in_hex_and_decimal() {
+        assert_eq!(get_byte(&mut args(&["0x1B"]), "--mapper").unwrap(), 0x1B);
+        assert_eq!(get_byte(&mut args(&["$1b"]), "--mapper").unwrap(), 0x1B);
+        assert_eq!(get_byte(&mut args(&["27"]), "--mapper").unwrap(), 27);
+        assert!(get_byte(&mut args(&["0x100"]), "--mapper").is_err());
+        assert!(get_byte(&mut args(&[]), "--mapper").is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_options() {
+        assert!(run(args(&["info", "game.gb", "--bogus"])).is_err());
+        assert!(run(args(&["info", "a.gb", "b.gb"])).is_err());
+        assert!(run(args(&[])).is_err());
+    }
+}
@@ -0,0 +1,280 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::joypad::joypad::Button;
+use crate::mappers::mapper::UnlicensedMapper;
+use crate::mappers::rtc::RtcMode;
+use crate::model::Model;
+use crate::rom::database::RomHashes;
+
+// Relative to the user's config folder, like ~/.config on Linux
+const CONFIG_FILE: &str = "gameboy-emulator/config.toml";
+
+const DEFAULT_KEY_BINDINGS: [(Button, &str); 8] = [
+    (Button::Up, "w"),
+    (Button::Left, "a"),
+    (Button::Down, "s"),
+    (Button::Right, "d"),
+    (Button::A, "j"),
+    (Button::B, "k"),
+    (Button::Select, "u"),
+    (Button::Start, "i"),
+];
+
+/**
+ * Settings for one game, or for every game in the global section. Settings that are None fall
+ * back to the global section, then to the defaults.
+ */
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct GameConfig {
+    pub palette: Option<Palette>,
+    // Console to emulate, picked from the header if None
+    pub model: Option<Model>,
+    // Mapper to use instead of the one in the header, for carts that lie about it
+    pub mapper: Option<MapperOverride>,
+    pub rtc_mode: Option<RtcMode>,
+    // Path to a DMG or MGB boot ROM to run before the game
+    pub boot_rom: Option<String>,
+    // Keyboard key for each button, buttons that aren't listed keep their default key
+    pub key_bindings: HashMap<Button, String>,
+}
+
+impl GameConfig {
+    /**
+     * Override these settings with the ones set in other
+     */
+    fn merge(&mut self, other: &GameConfig) {
+        self.palette = other.palette.or(self.palette);
+        self.model = other.model.or(self.model);
+        self.mapper = other.mapper.or(self.mapper);
+        self.rtc_mode = other.rtc_mode.or(self.rtc_mode);
+        self.boot_rom = other.boot_rom.clone().or(self.boot_rom.take());
+        self.key_bindings.extend(other.key_bindings.clone());
+    }
+
+    /**
+     * Get the button each key presses, the default keys with the ones in the config swapped in. A
+     * key can only press one button, so bindings that share a key are an error.
+     */
+    pub fn get_key_bindings(&self) -> io::Result<HashMap<String, Button>> {
+        let mut key_bindings: HashMap<String, Button> = HashMap::new();
+
+        for (button, key) in DEFAULT_KEY_BINDINGS {
+            let key = self.key_bindings.get(&button).map_or(key, String::as_str);
+            if let Some(other) = key_bindings.insert(key.to_string(), button) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "Invalid key bindings: {:?} and {:?} are both bound to {}",
+                        other, button, key
+                    ),
+                ));
+            }
+        }
+
+        Ok(key_bindings)
+    }
+}
+
+/**
+ * A cartridge type (0x0147) like 0x1B, or the name of a mapper that has no cartridge type
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum MapperOverride {
+    CartridgeType(u8),
+    Unlicensed(UnlicensedMapper),
+}
+
+/**
+ * The 4 shades of the screen from lightest to darkest, written as "#RRGGBB" in the config
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "[String; 4]")]
+pub struct Palette(pub [[u8; 4]; 4]);
+
+impl TryFrom<[String; 4]> for Palette {
+    type Error = String;
+
+    fn try_from(shades: [String; 4]) -> Result<Self, Self::Error> {
+        let mut palette = [[0, 0, 0, 0xFF]; 4];
+
+        for (colour, shade) in palette.iter_mut().zip(&shades) {
+            let hex = shade.strip_prefix('#').unwrap_or(shade);
+            let rgb = u32::from_str_radix(hex, 16)
+                .ok()
+                .filter(|_| hex.len() == 6)
+                .ok_or_else(|| format!("Invalid colour {}, expected #RRGGBB", shade))?;
+            colour[..3].copy_from_slice(&rgb.to_be_bytes()[1..]);
+        }
+
+        Ok(Palette(palette))
+    }
+}
+
+/**
+ * The config file, global settings at the top and a [games."<key>"] section per game. Games are
+ * keyed by the SHA-1 or CRC32 of the ROM, or by the title in the header.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    global: GameConfig,
+    games: HashMap<String, GameConfig>,
+    // No-Intro style DAT used to name games instead of the embedded one
+    dat: Option<String>,
+}
+
+impl Config {
+    /**
+     * Read the config from a path, or from the user's config folder. A missing file in the config
+     * folder is the same as an empty one.
+     */
+    pub fn load(path: Option<&str>) -> io::Result<Self> {
+        let path = match path {
+            Some(path) => PathBuf::from(path),
+            None => match get_default_path() {
+                Some(path) if path.is_file() => path,
+                _ => return Ok(Config::default()),
+            },
+        };
+
+        let text = fs::read_to_string(&path).map_err(|error| {
+            io::Error::new(
+                error.kind(),
+                format!("Failed to read config {}: {}", path.display(), error),
+            )
+        })?;
+        Config::parse(&text).map_err(|error| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid config {}: {}", path.display(), error),
+            )
+        })
+    }
+
+    /**
+     * The global settings are split from the games by hand, serde's flatten would let typos in
+     * them through
+     */
+    fn parse(text: &str) -> Result<Self, toml::de::Error> {
+        let mut table: toml::Table = toml::from_str(text)?;
+        let games = match table.remove("games") {
+            Some(games) => games.try_into()?,
+            None => HashMap::new(),
+        };
+        let dat = match table.remove("dat") {
+            Some(dat) => Some(dat.try_into()?),
+            None => None,
+        };
+
+        Ok(Config {
+            global: table.try_into()?,
+            games,
+            dat,
+        })
+    }
+
+    pub fn get_dat_path(&self) -> Option<&str> {
+        self.dat.as_deref()
+    }
+
+    /**
+     * Get the settings for a ROM. The most specific match wins when a ROM has several sections:
+     * the SHA-1, then the CRC32, then the title.
+     */
+    pub fn resolve(&self, rom: &[u8], title: &str) -> GameConfig {
+        let mut config = self.global.clone();
+        if self.games.is_empty() {
+            return config;
+        }
+
+        let hashes = RomHashes::new(rom);
+        for key in [title, &hashes.crc32, &hashes.sha1] {
+            for (_, game) in self
+                .games
+                .iter()
+                .filter(|(name, _)| name.eq_ignore_ascii_case(key))
+            {
+                config.merge(game);
+            }
+        }
+
+        config
+    }
+}
+
+fn get_default_path() -> Option<PathBuf> {
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+    Some(config_dir.join(CONFIG_FILE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mappers::mapper::get_mapper;
+    use crate::rom::cartridge::Cartridge;
+
+    #[test]
+    fn reads_dat_path() {
+        let config = Config::parse("dat = \"No-Intro.dat\"").unwrap();
+        assert_eq!(config.get_dat_path(), Some("No-Intro.dat"));
+        assert_eq!(Config::default().get_dat_path(), None);
+    }
+
+    #[test]
+    fn swaps_in_configured_keys() {
+        let config = Config::parse("[key-bindings]\na = \"z\"").unwrap();
+        let key_bindings = config.resolve(&[], "").get_key_bindings().unwrap();
+        assert_eq!(key_bindings.get("z"), Some(&Button::A));
+        assert_eq!(key_bindings.get("j"), None);
+        assert_eq!(key_bindings.get("k"), Some(&Button::B));
+    }
+
+    #[test]
+    fn rejects_keys_bound_twice() {
+        // B keeps its default key k
+        let config = Config::parse("[key-bindings]\na = \"k\"").unwrap();
+        let error = config.resolve(&[], "").get_key_bindings().unwrap_err();
+        assert!(error.to_string().contains("both bound to k"));
+    }
+
+    #[test]
+    fn reads_mapper_by_type_or_name() {
+        let config = Config::parse("mapper = 0x1B").unwrap();
+        let mapper = config.resolve(&[], "").mapper;
+        assert_eq!(mapper, Some(MapperOverride::CartridgeType(0x1B)));
+
+        let config = Config::parse("mapper = \"wisdom-tree\"").unwrap();
+        let mapper = config.resolve(&[], "").mapper;
+        assert_eq!(
+            mapper,
+            Some(MapperOverride::Unlicensed(UnlicensedMapper::WisdomTree))
+        );
+
+        assert!(Config::parse("mapper = \"mbc9\"").is_err());
+        assert!(Config::parse("mapper = 0x100").is_err());
+    }
+
+    #[test]
+    fn named_mapper_skips_detection() {
+        // Nothing in the ROM says Wisdom Tree, the first byte of each 32KiB bank is its number
+        let mut rom = vec![0; 0x10000];
+        rom[0x8000] = 1;
+        let config = Config::parse("mapper = \"wisdom-tree\"").unwrap();
+        let cartridge = Cartridge::from_data("game.gb".to_string(), rom, &config).unwrap();
+
+        let mut mapper = get_mapper(&cartridge).unwrap();
+        mapper.write(0x0001, 0x00);
+        assert_eq!(mapper.read(0x0000), 1);
+    }
+}
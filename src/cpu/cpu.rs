@@ -1,13 +1,66 @@
 use std::cell::Cell;
+use std::io;
 
 use crate::bus::bus::Bus;
 use crate::bus::interrupt_flags::{self, INTERRUPT_ENABLE_ADDR, INTERRUPT_FLAG_ADDR};
 use crate::bus::timer::{DIVIDER_REGISTER, TAC_REGISTER};
 use crate::cpu::registers::Registers;
 use crate::joypad::joypad::JOYPAD_REGISTER;
+use crate::model::Model;
 use crate::ppu::lcd::{LCD_CONTROL_REGISTER, LDC_STATUS_REGISTER};
+use crate::rom::boot_rom::BootRom;
 use crate::rom::cartridge::Cartridge;
 
+const SERIAL_CONTROL_REGISTER: u16 = 0xFF02;
+const NR52_REGISTER: u16 = 0xFF26;
+const LY_REGISTER: u16 = 0xFF44;
+const DMA_REGISTER: u16 = 0xFF46;
+
+// I/O registers as the DMG boot ROM leaves them when it jumps to the cartridge
+// https://gbdev.io/pandocs/Power_Up_Sequence.html#hardware-registers
+const DMG_IO_REGISTERS: [(u16, u8); 40] = [
+    (0xFF01, 0x00), // SB
+    (SERIAL_CONTROL_REGISTER, 0x7E),
+    (DIVIDER_REGISTER, 0xAB),
+    (0xFF05, 0x00), // TIMA
+    (0xFF06, 0x00), // TMA
+    (TAC_REGISTER, 0xF8),
+    (INTERRUPT_FLAG_ADDR, 0xE1),
+    (0xFF10, 0x80), // NR10
+    (0xFF11, 0xBF), // NR11
+    (0xFF12, 0xF3), // NR12
+    (0xFF13, 0xFF), // NR13
+    (0xFF14, 0xBF), // NR14
+    (0xFF16, 0x3F), // NR21
+    (0xFF17, 0x00), // NR22
+    (0xFF18, 0xFF), // NR23
+    (0xFF19, 0xBF), // NR24
+    (0xFF1A, 0x7F), // NR30
+    (0xFF1B, 0xFF), // NR31
+    (0xFF1C, 0x9F), // NR32
+    (0xFF1D, 0xFF), // NR33
+    (0xFF1E, 0xBF), // NR34
+    (0xFF20, 0xFF), // NR41
+    (0xFF21, 0x00), // NR42
+    (0xFF22, 0x00), // NR43
+    (0xFF23, 0xBF), // NR44
+    (0xFF24, 0x77), // NR50
+    (0xFF25, 0xF3), // NR51
+    (NR52_REGISTER, 0xF1),
+    (LCD_CONTROL_REGISTER, 0x91),
+    (LDC_STATUS_REGISTER, 0x85),
+    (0xFF42, 0x00), // SCY
+    (0xFF43, 0x00), // SCX
+    (LY_REGISTER, 0x00),
+    (0xFF45, 0x00), // LYC
+    (DMA_REGISTER, 0xFF),
+    (0xFF47, 0xFC), // BGP
+    (0xFF48, 0xFF), // OBP0, left uninitialized by the boot ROM
+    (0xFF49, 0xFF), // OBP1, left uninitialized by the boot ROM
+    (0xFF4A, 0x00), // WY
+    (0xFF4B, 0x00), // WX
+];
+
 #[derive(Debug)]
 pub struct CPU {
     registers: Registers,
@@ -24,35 +77,70 @@ pub struct CPU {
     ime_flag: bool,
     // Used to delay the effect of EI instruction by one instruction
     previous_ime_flag: bool,
+    // Set by STOP, nothing runs until a button on a selected joypad line is pressed
+    is_stopped: bool,
 }
 
 /**
  * For each instruction, we need to emulate the function + addressing mode + cycles
 */
 impl CPU {
-    pub fn new(cartridge: &Cartridge) -> Self {
+    /**
+     * With a boot ROM the CPU starts at 0x0000 and runs it, otherwise it starts at the cartridge
+     * entry point in the state the boot ROM would have left
+     */
+    pub fn new(cartridge: &Cartridge, boot_rom: Option<BootRom>, model: Model) -> io::Result<Self> {
+        let has_boot_rom = boot_rom.is_some();
+        let registers = if has_boot_rom {
+            Registers::power_on()
+        } else {
+            Registers::new(model, cartridge)
+        };
+
         let mut cpu = CPU {
-            registers: Registers::new(),
+            registers,
             cycles: Cell::new(0),
-            bus: Bus::new(cartridge),
+            bus: Bus::new(cartridge, boot_rom, model)?,
             ime_flag: false, // IME is unset (interrupts are disabled) when the game starts running.
             previous_ime_flag: false,
+            is_stopped: false,
         };
 
-        cpu.boot();
-        return cpu;
+        if !has_boot_rom {
+            cpu.boot();
+        }
+        return Ok(cpu);
     }
 
     fn boot(&mut self) {
-        // https://gbdev.io/pandocs/Power_Up_Sequence.html?highlight=boot#hardware-registers
-        self.bus.write_byte(DIVIDER_REGISTER, 0xAB);
-        self.bus.write_byte(TAC_REGISTER, 0xF8);
-        self.bus.write_byte(INTERRUPT_FLAG_ADDR, 0xE1);
-        self.bus.write_byte(LCD_CONTROL_REGISTER, 0x91);
-        self.bus.write_byte(LDC_STATUS_REGISTER, 0x85);
+        self.bus.skip_boot_rom();
+
+        // Written directly, writes through the bus would reset DIV and start a DMA transfer
+        for (addr, value) in DMG_IO_REGISTERS {
+            *self.bus.get_pointer(addr) = value;
+        }
+
+        // The SGB and CGB boot ROMs take a different time depending on the cartridge, so where
+        // they leave DIV isn't known and it starts at 0
+        let model_registers: &[(u16, u8)] = match self.bus.get_model() {
+            Model::Dmg0 => &[
+                (DIVIDER_REGISTER, 0x18),
+                (LDC_STATUS_REGISTER, 0x81),
+                (LY_REGISTER, 0x91),
+            ],
+            Model::Dmg | Model::Mgb => &[],
+            Model::Sgb | Model::Sgb2 => &[(DIVIDER_REGISTER, 0x00), (NR52_REGISTER, 0xF0)],
+            Model::Cgb | Model::Agb => &[
+                (DIVIDER_REGISTER, 0x00),
+                (SERIAL_CONTROL_REGISTER, 0x7F),
+                (DMA_REGISTER, 0x00),
+            ],
+        };
+        for &(addr, value) in model_registers {
+            *self.bus.get_pointer(addr) = value;
+        }
+
         self.bus.write_byte(JOYPAD_REGISTER as u16, 0xCF);
-        self.bus.write_byte(0xFF46, 0xFF);
-        self.bus.write_byte(0xFF47, 0xFC);
     }
 
     #[allow(dead_code, reason = "Debugging function")]
@@ -91,6 +179,13 @@ impl CPU {
 
     pub fn step(&mut self) -> usize {
         let cycles_before = self.cycles.get();
+        if self.is_stopped {
+            if !self.is_joypad_pressed() {
+                self.increment_cycles(1);
+                return self.cycles.get() - cycles_before;
+            }
+            self.is_stopped = false;
+        }
         self.previous_ime_flag = self.ime_flag;
 
         let opcode = self.next_byte();
@@ -432,6 +527,10 @@ impl CPU {
         if self.ime_flag != true || self.previous_ime_flag != true {
             return;
         }
+        // Only the joypad wakes the CPU from STOP
+        if self.is_stopped {
+            return;
+        }
 
         // Handle multiple or nested interrupts by priority
         while self.ime_flag {
@@ -569,8 +668,28 @@ impl CPU {
         // TODO: Halt bug is not implemented
     }
 
-    fn stop(&self) {
-        unimplemented!();
+    fn stop(&mut self) {
+        // STOP is followed by a byte that is skipped
+        self.next_byte();
+        self.increment_cycles(1);
+        self.bus.write_byte(DIVIDER_REGISTER, 0);
+
+        if self.bus.is_speed_switch_armed() {
+            self.bus.switch_speed();
+            return;
+        }
+
+        // Low power mode until a button is pressed
+        self.is_stopped = !self.is_joypad_pressed();
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.is_stopped
+    }
+
+    // Pressed buttons on the selected lines pull the low bits of the joypad register low
+    fn is_joypad_pressed(&self) -> bool {
+        self.bus.read_byte(JOYPAD_REGISTER as u16) & 0x0F != 0x0F
     }
 
     fn jr(&self, n: i8) {
@@ -1180,3 +1299,87 @@ impl CPU {
 fn load(dest: &mut u8, source: u8) {
     *dest = source;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::joypad::joypad::Button;
+
+    // STOP at the entry point followed by a NOP
+    fn get_cpu() -> CPU {
+        let mut rom = vec![0; 0x8000];
+        rom[0x0100] = 0x10;
+        rom[0x0102] = 0x00;
+        let cartridge =
+            Cartridge::from_data("stop.gb".to_string(), rom, &Config::default()).unwrap();
+        CPU::new(&cartridge, None, Model::Dmg).unwrap()
+    }
+
+    #[test]
+    fn stop_waits_for_a_selected_button() {
+        let mut cpu = get_cpu();
+        // Select the action buttons
+        cpu.bus.write_byte(JOYPAD_REGISTER as u16, 0x10);
+
+        cpu.step();
+        assert!(cpu.is_stopped());
+        assert_eq!(cpu.registers.pc.get(), 0x0102);
+
+        // The d-pad isn't selected so it doesn't wake the CPU
+        cpu.bus.joypad.press_button(Button::Up, true);
+        assert_eq!(cpu.step(), 1);
+        assert!(cpu.is_stopped());
+        assert_eq!(cpu.registers.pc.get(), 0x0102);
+
+        cpu.bus.joypad.press_button(Button::A, true);
+        cpu.step();
+        assert!(!cpu.is_stopped());
+        assert_eq!(cpu.registers.pc.get(), 0x0103);
+    }
+
+    #[test]
+    fn stop_with_a_pressed_button_continues() {
+        let mut cpu = get_cpu();
+        cpu.bus.write_byte(JOYPAD_REGISTER as u16, 0x10);
+        cpu.bus.joypad.press_button(Button::Start, true);
+
+        cpu.step();
+        assert!(!cpu.is_stopped());
+        assert_eq!(cpu.registers.pc.get(), 0x0102);
+    }
+
+    #[test]
+    fn starts_in_post_boot_state_without_boot_rom() {
+        let cpu = get_cpu();
+        assert_eq!(cpu.registers.a.get(), 0x01);
+        assert_eq!(cpu.registers.sp.get(), 0xFFFE);
+        assert_eq!(cpu.registers.pc.get(), 0x0100);
+        assert_eq!(cpu.bus.read_byte(LCD_CONTROL_REGISTER), 0x91);
+        assert_eq!(cpu.bus.read_byte(LDC_STATUS_REGISTER) & 0x03, 0x01);
+    }
+
+    #[test]
+    fn boot_rom_is_mapped_until_disabled() {
+        // LD A, 0x01 then LDH (0x50), A
+        let mut data = vec![0; 0x100];
+        data[0..4].copy_from_slice(&[0x3E, 0x01, 0xE0, 0x50]);
+        let path = std::env::temp_dir().join("cpu_boot_rom.bin");
+        std::fs::write(&path, data).unwrap();
+        let boot_rom = BootRom::load(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let mut rom = vec![0; 0x8000];
+        rom[0] = 0xAA;
+        let cartridge =
+            Cartridge::from_data("boot.gb".to_string(), rom, &Config::default()).unwrap();
+        let mut cpu = CPU::new(&cartridge, Some(boot_rom), Model::Dmg).unwrap();
+        assert_eq!(cpu.registers.pc.get(), 0x0000);
+        assert_eq!(cpu.bus.read_byte(0x0000), 0x3E);
+
+        cpu.step();
+        cpu.step();
+        assert_eq!(cpu.registers.pc.get(), 0x0004);
+        assert_eq!(cpu.bus.read_byte(0x0000), 0xAA);
+    }
+}
@@ -1,5 +1,12 @@
 use std::cell::Cell;
 
+use crate::model::Model;
+use crate::rom::cartridge::Cartridge;
+use crate::rom::rom_header::{CgbFlag, OldLicenseCode, TITLE_START};
+
+// Title checksums the CGB boot ROM leaves a different HL for
+const HL_QUIRK_TITLE_CHECKSUMS: [u8; 2] = [0x43, 0x58];
+
 #[derive(Debug)]
 pub struct Registers {
     pub a: Cell<u8>,
@@ -15,23 +22,63 @@ pub struct Registers {
 }
 
 impl Registers {
-    pub fn new() -> Self {
+    /**
+     * The registers as the model's boot ROM leaves them
+     * https://gbdev.io/pandocs/Power_Up_Sequence.html#cpu-registers
+     */
+    pub fn new(model: Model, cartridge: &Cartridge) -> Self {
+        let header = &cartridge.rom_header;
+        // The DMG boot ROM's checksum loop ends with an ADD, which leaves H and C set unless the
+        // header checksum is 0
+        let dmg_flags = if header.header_checksum == 0 {
+            0x80
+        } else {
+            0xB0
+        };
+
+        // [A, F, B, C, D, E, H, L]
+        let values = match model {
+            Model::Dmg0 => [0x01, 0x00, 0xFF, 0x13, 0x00, 0xC1, 0x84, 0x03],
+            Model::Dmg => [0x01, dmg_flags, 0x00, 0x13, 0x00, 0xD8, 0x01, 0x4D],
+            Model::Mgb => [0xFF, dmg_flags, 0x00, 0x13, 0x00, 0xD8, 0x01, 0x4D],
+            Model::Sgb => [0x01, 0x00, 0x00, 0x14, 0x00, 0x00, 0xC0, 0x60],
+            Model::Sgb2 => [0xFF, 0x00, 0x00, 0x14, 0x00, 0x00, 0xC0, 0x60],
+            Model::Cgb | Model::Agb => get_cgb_values(model, cartridge),
+        };
+        let [a, f, b, c, d, e, h, l] = values;
+
         Registers {
-            // TODO: These values may not be correct depending on the game.
-            // https://gbdev.io/pandocs/Power_Up_Sequence.html#cpu-registers
-            a: Cell::new(0x01),
-            b: Cell::new(0x00),
-            c: Cell::new(0x13),
-            d: Cell::new(0x00),
-            e: Cell::new(0xD8),
-            f: Cell::new(0xB0),
-            h: Cell::new(0x01),
-            l: Cell::new(0x4D),
+            a: Cell::new(a),
+            b: Cell::new(b),
+            c: Cell::new(c),
+            d: Cell::new(d),
+            e: Cell::new(e),
+            f: Cell::new(f),
+            h: Cell::new(h),
+            l: Cell::new(l),
             sp: Cell::new(0xFFFE),
             pc: Cell::new(0x0100),
         }
     }
 
+    /**
+     * The registers when the console is switched on, the boot ROM sets them up from here
+     */
+    pub fn power_on() -> Self {
+        Registers {
+            a: Cell::new(0),
+            b: Cell::new(0),
+            c: Cell::new(0),
+            d: Cell::new(0),
+            e: Cell::new(0),
+            f: Cell::new(0),
+            h: Cell::new(0),
+            l: Cell::new(0),
+            sp: Cell::new(0),
+            pc: Cell::new(0),
+        }
+    }
+
     pub fn get_bc(&self) -> u16 {
         (self.b.get() as u16) << 8 | self.c.get() as u16
     }
@@ -118,3 +165,51 @@ impl Registers {
         return (self.f.get() & 0x10) != 0;
     }
 }
+
+/**
+ * The CGB boot ROM leaves different values for DMG cartridges, where B is the title checksum it
+ * uses to pick a palette. The AGB boot ROM is the same with an extra INC B at the end.
+ */
+fn get_cgb_values(model: Model, cartridge: &Cartridge) -> [u8; 8] {
+    let header = &cartridge.rom_header;
+    let [a, mut f, mut b, c, d, e, h, l] = if header.cgb_flag == CgbFlag::DmgOnly {
+        let title_checksum = get_title_checksum(cartridge);
+        let (h, l) = if HL_QUIRK_TITLE_CHECKSUMS.contains(&title_checksum) {
+            (0x99, 0x1A)
+        } else {
+            (0x00, 0x7C)
+        };
+        [0x11, 0x80, title_checksum, 0x00, 0x00, 0x08, h, l]
+    } else {
+        [0x11, 0x80, 0x00, 0x00, 0xFF, 0x56, 0x00, 0x0D]
+    };
+
+    if model == Model::Agb {
+        // INC B sets Z and H from the result and leaves C alone
+        let half_carry = if b & 0x0F == 0x0F { 0x20 } else { 0x00 };
+        b = b.wrapping_add(1);
+        let zero = if b == 0 { 0x80 } else { 0x00 };
+        f = zero | half_carry | (f & 0x10);
+    }
+
+    [a, f, b, c, d, e, h, l]
+}
+
+/**
+ * Sum of the title bytes, the CGB boot ROM only computes it for Nintendo's games
+ */
+fn get_title_checksum(cartridge: &Cartridge) -> u8 {
+    let header = &cartridge.rom_header;
+    let is_nintendo = match header.old_license_code {
+        OldLicenseCode::Nintendo => true,
+        OldLicenseCode::NewLicenseCode => header.new_licensee_code.as_deref() == Some("01"),
+        _ => false,
+    };
+    if !is_nintendo {
+        return 0;
+    }
+
+    cartridge.get_data()[TITLE_START..TITLE_START + 16]
+        .iter()
+        .fold(0, |checksum: u8, byte| checksum.wrapping_add(*byte))
+}
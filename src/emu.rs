@@ -1,3 +1,5 @@
+use std::io;
+
 use crate::{
     bus::{
         interrupt_flags::{self, InterruptType},
@@ -5,10 +7,18 @@ use crate::{
     },
     cpu::cpu::CPU,
     joypad::joypad::Button,
+    mappers::{
+        camera::CameraSensor, infrared::InfraredTransport, mapper::has_battery, rtc::RtcMode,
+        speaker::TonePlayer,
+    },
+    model::Model,
     ppu::lcd::{BUFFER_SIZE, Lcd},
-    rom::cartridge::Cartridge,
+    rom::{boot_rom::BootRom, cartridge::Cartridge, save::SaveFile},
 };
 
+// M-cycles taken by the `JR -2` the boot ROM spins on when it locks up
+const LOCKED_UP_CYCLES: usize = 3;
+
 #[derive(Debug)]
 pub struct Context {
     is_running: bool,
@@ -16,17 +26,79 @@ pub struct Context {
     cpu: CPU,
     timer: Timer,
     lcd: Lcd,
+    model: Model,
+    // Whether the rumble motor was on at any point during the last frame
+    is_rumbling: bool,
+    is_rumbling_this_frame: bool,
+    // Only cartridges with a battery are saved
+    save_file: Option<SaveFile>,
+    // The boot ROM rejected the cartridge, so the game never starts
+    is_locked_up: bool,
+    // Half an M-cycle left over in double speed
+    leftover_cycles: usize,
 }
 
 impl Context {
-    pub fn new(cartridge: Cartridge) -> Self {
-        Context {
+    /**
+     * The boot ROM is optional, without it the game starts right away. The model is taken from
+     * the config when it is None, or picked from the header if the config doesn't set one.
+     */
+    pub fn new(
+        cartridge: Cartridge,
+        boot_rom: Option<BootRom>,
+        model: Option<Model>,
+    ) -> io::Result<Self> {
+        let config = cartridge.get_config();
+        let model = model
+            .or(config.model)
+            .unwrap_or_else(|| Model::detect(&cartridge.rom_header));
+        // Only DMG and MGB boot ROMs are supported, a CGB would run them as a DMG
+        if boot_rom.is_some() && model.is_cgb() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "The {:?} model needs a CGB boot ROM, which isn't supported. Pick a DMG \
+                     model or leave out the boot ROM.",
+                    model
+                ),
+            ));
+        }
+        let timer = match boot_rom {
+            Some(_) => Timer::new(),
+            None => Timer::after_boot(model),
+        };
+        let mut cpu = CPU::new(&cartridge, boot_rom, model)?;
+
+        let mut save_file = None;
+        if has_battery(&cartridge) {
+            let mut file = SaveFile::new(cartridge.get_file_name());
+            if let Some(data) = file.load()? {
+                cpu.bus.load_save_data(&data);
+            }
+            save_file = Some(file);
+        }
+
+        if let Some(mode) = config.rtc_mode {
+            cpu.bus.set_rtc_mode(mode);
+        }
+        let mut lcd = Lcd::new(model);
+        if let Some(palette) = config.palette {
+            lcd.set_palette(palette.0);
+        }
+
+        Ok(Context {
             is_running: false,
             is_paused: false,
-            cpu: CPU::new(&cartridge),
-            timer: Timer::new(),
-            lcd: Lcd::new(),
-        }
+            cpu,
+            timer,
+            lcd,
+            model,
+            is_rumbling: false,
+            is_rumbling_this_frame: false,
+            save_file,
+            is_locked_up: false,
+            leftover_cycles: 0,
+        })
     }
 
     pub fn start(&mut self) {
@@ -35,10 +107,22 @@ impl Context {
     }
 
     pub fn stop(&mut self) {
-        // TODO: Save the game here
+        if let Err(error) = self.save() {
+            eprintln!("Failed to save the game: {}", error);
+        }
         self.is_running = false;
     }
 
+    /**
+     * Write battery backed RAM to the save file, does nothing for cartridges without a battery
+     */
+    pub fn save(&mut self) -> io::Result<()> {
+        if let Some(save_file) = self.save_file.as_mut() {
+            save_file.save(&self.cpu.bus.get_save_data())?;
+        }
+        return Ok(());
+    }
+
     pub fn pause(&mut self) {
         self.is_paused = true;
     }
@@ -52,13 +136,116 @@ impl Context {
             return None;
         }
 
-        let cycle_diff = self.cpu.step();
-        self.timer.update_timer(&mut self.cpu.bus, cycle_diff);
+        // The boot ROM hangs in a `JR -2` loop with interrupts disabled
+        let cycle_diff = if self.is_locked_up {
+            LOCKED_UP_CYCLES
+        } else {
+            self.cpu.step()
+        };
+        // DIV and the timer are stopped along with the CPU
+        if !self.cpu.is_stopped() {
+            self.timer.update_timer(&mut self.cpu.bus, cycle_diff);
+        }
+        // The CPU and the timer run twice as fast in double speed, the rest of the console doesn't
+        let cycle_diff = self.get_normal_speed_cycles(cycle_diff);
+        self.cpu.bus.update_mapper(cycle_diff);
         let buffer = self.lcd.update_graphics(&mut self.cpu.bus, cycle_diff);
-        self.cpu.handle_interrupts();
+        if !self.is_locked_up {
+            self.cpu.handle_interrupts();
+        }
+
+        self.is_rumbling_this_frame |= self.cpu.bus.is_rumbling();
+        if buffer.is_some() {
+            self.is_rumbling = self.is_rumbling_this_frame;
+            self.is_rumbling_this_frame = false;
+            self.update_save_file();
+        }
+
         return buffer;
     }
 
+    fn get_normal_speed_cycles(&mut self, cycles: usize) -> usize {
+        if !self.cpu.bus.is_double_speed() {
+            return cycles;
+        }
+
+        let cycles = cycles + self.leftover_cycles;
+        self.leftover_cycles = cycles % 2;
+        cycles / 2
+    }
+
+    fn update_save_file(&mut self) {
+        let was_written = self.cpu.bus.take_ram_written();
+        let should_save = self
+            .save_file
+            .as_mut()
+            .is_some_and(|save_file| save_file.update(was_written));
+
+        // Save periodically so a crash doesn't lose progress
+        if should_save && let Err(error) = self.save() {
+            eprintln!("Failed to save the game: {}", error);
+        }
+    }
+
+    #[allow(dead_code, reason = "Frontend API, the window doesn't show the model")]
+    pub fn get_model(&self) -> Model {
+        return self.model;
+    }
+
+    /**
+     * Hang like the boot ROM does on a cartridge with a bad logo or header checksum
+     */
+    pub fn lock_up(&mut self) {
+        self.is_locked_up = true;
+    }
+
+    /**
+     * Choose whether the cartridge clock follows emulated time or the host's clock
+     */
+    pub fn set_rtc_mode(&mut self, mode: RtcMode) {
+        self.cpu.bus.set_rtc_mode(mode);
+    }
+
+    /**
+     * Games pulse the motor to control its strength, so this is set if the motor was on at any
+     * point during the last frame
+     */
+    #[allow(dead_code, reason = "Frontend API, the window has no way to rumble")]
+    pub fn is_rumbling(&self) -> bool {
+        return self.is_rumbling;
+    }
+
+    /**
+     * Tilt the cartridge for games with an accelerometer, in g. Positive x tilts right and
+     * positive y tilts down.
+     */
+    pub fn set_tilt(&mut self, x: f32, y: f32) {
+        self.cpu.bus.set_tilt(x, y);
+    }
+
+    /**
+     * Set where the Game Boy Camera gets its frames from, an image file or a callback
+     */
+    pub fn set_camera_sensor(&mut self, sensor: Box<dyn CameraSensor>) {
+        self.cpu.bus.set_camera_sensor(sensor);
+    }
+
+    /**
+     * Connect the infrared port of HuC1 and HuC3 cartridges
+     */
+    #[allow(dead_code, reason = "Frontend API, the window has no IR link yet")]
+    pub fn set_infrared_transport(&mut self, transport: Box<dyn InfraredTransport>) {
+        self.cpu.bus.set_infrared_transport(transport);
+    }
+
+    /**
+     * Connect the speaker of HuC3 cartridges
+     */
+    #[allow(dead_code, reason = "Frontend API, the window has no audio output yet")]
+    pub fn set_tone_player(&mut self, player: Box<dyn TonePlayer>) {
+        self.cpu.bus.set_tone_player(player);
+    }
+
     pub fn press_button(&mut self, button: Button, is_pressed: bool) {
         let should_request_interrupt = self.cpu.bus.joypad.press_button(button, is_pressed);
 
@@ -67,3 +254,34 @@ impl Context {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn get_cartridge() -> Cartridge {
+        Cartridge::from_data("emu.gb".to_string(), vec![0; 0x8000], &Config::default()).unwrap()
+    }
+
+    #[test]
+    fn rejects_dmg_boot_rom_on_cgb() {
+        let path = std::env::temp_dir().join("emu_dmg_boot.bin");
+        std::fs::write(&path, [0; 0x100]).unwrap();
+        let boot_rom = BootRom::load(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let error =
+            Context::new(get_cartridge(), Some(boot_rom.clone()), Some(Model::Cgb)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(Context::new(get_cartridge(), Some(boot_rom), Some(Model::Dmg)).is_ok());
+    }
+
+    #[test]
+    fn model_comes_from_header_by_default() {
+        let context = Context::new(get_cartridge(), None, None).unwrap();
+        assert_eq!(context.get_model(), Model::Dmg);
+        let context = Context::new(get_cartridge(), None, Some(Model::Mgb)).unwrap();
+        assert_eq!(context.get_model(), Model::Mgb);
+    }
+}
@@ -1,3 +1,5 @@
+use serde::Deserialize;
+
 use crate::utils::{clear_bit, set_bit, test_bit};
 
 pub const JOYPAD_REGISTER: usize = 0xFF00;
@@ -8,7 +10,8 @@ pub const JOYPAD_REGISTER: usize = 0xFF00;
 //  START_DOWN = 3,
 //  SELECT_D_PAD = 4,
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
 #[repr(u8)]
 pub enum Button {
     A = 0,
@@ -1,29 +1,24 @@
 mod bus;
+mod cli;
+mod config;
 mod cpu;
 mod emu;
 mod joypad;
 mod mappers;
+mod model;
 mod ppu;
 mod rom;
 mod ui;
 mod utils;
 
-use crate::rom::cartridge::Cartridge;
+use std::process::ExitCode;
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args: Vec<String> = std::env::args().collect();
-
-    if args.len() < 2 {
-        panic!("Not enough arguments provided");
+fn main() -> ExitCode {
+    match cli::run(std::env::args().skip(1)) {
+        Ok(exit_code) => exit_code,
+        Err(error) => {
+            eprintln!("{}", error);
+            ExitCode::FAILURE
+        }
     }
-
-    let file_path = args.get(1).unwrap();
-
-    let cartridge = Cartridge::new(&file_path)?;
-
-    let context = emu::Context::new(cartridge);
-    let mut ui = ui::UI::new(context);
-    ui.start();
-
-    Ok(())
 }
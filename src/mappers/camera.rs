@@ -0,0 +1,424 @@
+use std::fmt;
+use std::io;
+
+use image::imageops::{self, FilterType};
+
+use crate::mappers::mapper::{Mapper, RAM_BANK_SIZE, load_ram, read_rom_bank};
+use crate::rom::cartridge::Cartridge;
+use crate::utils::test_bit;
+
+pub const SENSOR_WIDTH: usize = 128;
+pub const SENSOR_HEIGHT: usize = 112;
+pub const SENSOR_PIXELS: usize = SENSOR_WIDTH * SENSOR_HEIGHT;
+
+// 16 banks of 8KiB, the photo album lives here
+const RAM_SIZE: usize = 0x20000;
+// Setting bit 4 of the RAM bank register maps the camera registers instead of RAM
+const REGISTER_BANK_BIT: u8 = 4;
+const REGISTER_COUNT: usize = 0x36;
+
+// Camera registers (0xA000 - 0xA035)
+const CONTROL_REGISTER: usize = 0x00;
+const GAIN_REGISTER: usize = 0x01;
+const EXPOSURE_HIGH_REGISTER: usize = 0x02;
+const EXPOSURE_LOW_REGISTER: usize = 0x03;
+const EDGE_REGISTER: usize = 0x04;
+// 4x4 matrix of 3 thresholds each
+const DITHER_MATRIX_START: usize = 0x06;
+
+// Bits in the control register
+const CAPTURE_BIT: u8 = 0;
+// Bits in the gain register
+const NO_NEGATIVE_BIT: u8 = 7;
+// Bits in the edge register
+const INVERT_BIT: u8 = 3;
+
+// The captured image is written as 16x14 tiles to the start of RAM bank 0
+const IMAGE_ADDRESS: usize = 0x0100;
+
+// Capturing takes 32446 M-cycles, plus 512 without the N bit, plus 16 per exposure step
+const CAPTURE_BASE_CYCLES: usize = 32446;
+const CAPTURE_NO_N_CYCLES: usize = 512;
+const CAPTURE_EXPOSURE_CYCLES: usize = 16;
+
+// An exposure of 0x0800 passes the sensor input through unchanged
+const NEUTRAL_EXPOSURE: f32 = 0x0800 as f32;
+const EDGE_RATIOS: [f32; 8] = [0.5, 0.75, 1.0, 1.25, 2.0, 3.0, 4.0, 5.0];
+
+/**
+ * Source of the frames the camera sees. Frames are greyscale, row major, 0 is black and 255 is white.
+ */
+pub trait CameraSensor {
+    fn capture(&mut self, frame: &mut [u8; SENSOR_PIXELS]);
+}
+
+impl fmt::Debug for dyn CameraSensor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CameraSensor")
+    }
+}
+
+// Any closure can be used as a sensor, frontends can use this to feed frames from a webcam
+impl<F: FnMut(&mut [u8; SENSOR_PIXELS])> CameraSensor for F {
+    fn capture(&mut self, frame: &mut [u8; SENSOR_PIXELS]) {
+        self(frame)
+    }
+}
+
+/**
+ * Sensor that always sees the same image, loaded from a file and scaled to the sensor size
+ */
+#[derive(Debug)]
+pub struct ImageFileSensor {
+    frame: Vec<u8>,
+}
+
+impl ImageFileSensor {
+    pub fn new(path: &str) -> io::Result<Self> {
+        let image = image::open(path)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?
+            .into_luma8();
+
+        let frame = imageops::resize(
+            &image,
+            SENSOR_WIDTH as u32,
+            SENSOR_HEIGHT as u32,
+            FilterType::Triangle,
+        );
+
+        Ok(ImageFileSensor {
+            frame: frame.into_raw(),
+        })
+    }
+}
+
+impl CameraSensor for ImageFileSensor {
+    fn capture(&mut self, frame: &mut [u8; SENSOR_PIXELS]) {
+        frame.copy_from_slice(&self.frame);
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct PocketCamera {
+    rom_data: Vec<u8>,
+    ram: Vec<u8>,
+    // Set when the game writes to RAM, used to know when to save
+    is_ram_written: bool,
+    // RAM can always be read, but only written to when enabled
+    is_ram_enabled: bool,
+    // 6 bit register, bank 0 can be mapped to 0x4000 - 0x7FFF
+    rom_bank: u8,
+    ram_bank: u8,
+    registers: [u8; REGISTER_COUNT],
+    // M-cycles until the capture in progress is done
+    capture_cycles: Option<usize>,
+    sensor: Option<Box<dyn CameraSensor>>,
+}
+
+impl Mapper for PocketCamera {
+    fn new(cartridge: &Cartridge) -> Self {
+        PocketCamera {
+            rom_data: cartridge.get_data().to_vec(),
+            ram: vec![0; RAM_SIZE],
+            is_ram_written: false,
+            is_ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            registers: [0; REGISTER_COUNT],
+            capture_cycles: None,
+            sensor: None,
+        }
+    }
+
+    fn read(&self, addr: u16) -> u8 {
+        let addr = addr as usize;
+
+        match addr {
+            0x0000..0x4000 => read_rom_bank(&self.rom_data, 0, addr),
+            0x4000..0x8000 => read_rom_bank(&self.rom_data, self.rom_bank as usize, addr - 0x4000),
+            0xA000..0xC000 => {
+                if self.is_register_bank_selected() {
+                    // Only the control register can be read, the rest read as 0
+                    return match (addr - 0xA000) & 0x7F {
+                        CONTROL_REGISTER => self.registers[CONTROL_REGISTER],
+                        _ => 0x00,
+                    };
+                }
+
+                self.ram[self.get_ram_address(addr)]
+            }
+            _ => panic!("Out of bank range"),
+        }
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        let addr = addr as usize;
+
+        match addr {
+            0x0000..0x2000 => {
+                self.is_ram_enabled = value & 0x0F == 0x0A;
+            }
+            0x2000..0x4000 => {
+                self.rom_bank = value & 0x3F;
+            }
+            0x4000..0x6000 => {
+                self.ram_bank = value & 0x1F;
+            }
+            0x6000..0x8000 => {
+                // No registers here
+            }
+            0xA000..0xC000 => {
+                if self.is_register_bank_selected() {
+                    // Registers are mirrored every 0x80 bytes
+                    self.write_register((addr - 0xA000) & 0x7F, value);
+                    return;
+                }
+
+                if self.is_ram_enabled {
+                    let ram_addr = self.get_ram_address(addr);
+                    self.ram[ram_addr] = value;
+                    self.is_ram_written = true;
+                }
+            }
+            _ => panic!("Out of bank range"),
+        }
+    }
+
+    fn step(&mut self, cycles: usize) {
+        let Some(capture_cycles) = self.capture_cycles else {
+            return;
+        };
+
+        if capture_cycles > cycles {
+            self.capture_cycles = Some(capture_cycles - cycles);
+            return;
+        }
+
+        self.capture_cycles = None;
+        self.registers[CONTROL_REGISTER] &= !(1 << CAPTURE_BIT);
+        self.capture();
+    }
+
+    fn set_camera_sensor(&mut self, sensor: Box<dyn CameraSensor>) {
+        self.sensor = Some(sensor);
+    }
+
+    fn take_ram_written(&mut self) -> bool {
+        std::mem::take(&mut self.is_ram_written)
+    }
+
+    fn get_save_data(&self) -> Vec<u8> {
+        self.ram.clone()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data);
+    }
+}
+
+impl PocketCamera {
+    fn is_register_bank_selected(&self) -> bool {
+        test_bit(self.ram_bank, REGISTER_BANK_BIT)
+    }
+
+    fn get_ram_address(&self, addr: usize) -> usize {
+        let bank = (self.ram_bank & 0x0F) as usize;
+        bank * RAM_BANK_SIZE + (addr - 0xA000)
+    }
+
+    fn write_register(&mut self, register: usize, value: u8) {
+        match register {
+            CONTROL_REGISTER => {
+                // Only the capture bit and the 2 mode bits exist
+                let value = value & 0x07;
+                let is_capture_requested = test_bit(value, CAPTURE_BIT);
+                self.registers[CONTROL_REGISTER] = value;
+
+                if is_capture_requested && self.capture_cycles.is_none() {
+                    self.capture_cycles = Some(self.get_capture_cycles());
+                } else if !is_capture_requested {
+                    // Clearing the bit cancels the capture
+                    self.capture_cycles = None;
+                }
+            }
+            1..REGISTER_COUNT => self.registers[register] = value,
+            _ => {}
+        }
+    }
+
+    fn get_exposure(&self) -> u16 {
+        u16::from_be_bytes([
+            self.registers[EXPOSURE_HIGH_REGISTER],
+            self.registers[EXPOSURE_LOW_REGISTER],
+        ])
+    }
+
+    fn get_capture_cycles(&self) -> usize {
+        let n_cycles = if test_bit(self.registers[GAIN_REGISTER], NO_NEGATIVE_BIT) {
+            0
+        } else {
+            CAPTURE_NO_N_CYCLES
+        };
+
+        CAPTURE_BASE_CYCLES + n_cycles + CAPTURE_EXPOSURE_CYCLES * self.get_exposure() as usize
+    }
+
+    /**
+     * Approximation of the M64282FP pipeline: the sensor frame is scaled by exposure and gain,
+     * edge enhanced, then quantized to 2 bits per pixel using the dither matrix
+     */
+    fn capture(&mut self) {
+        let mut frame = [0x80; SENSOR_PIXELS];
+        if let Some(sensor) = self.sensor.as_mut() {
+            sensor.capture(&mut frame);
+        }
+
+        let gain_register = self.registers[GAIN_REGISTER];
+        let edge_register = self.registers[EDGE_REGISTER];
+        // Gain is roughly logarithmic, every step is about 1.5 dB
+        let gain = 2f32.powf((gain_register & 0x1F) as f32 / 4.0) / 2f32.powf(0x10 as f32 / 4.0);
+        let exposure = self.get_exposure() as f32 / NEUTRAL_EXPOSURE;
+        // VH bits select the edge enhancement direction
+        let vh = (gain_register >> 5) & 0x03;
+        let edge_ratio = EDGE_RATIOS[((edge_register >> 4) & 0x07) as usize];
+        let is_inverted = test_bit(edge_register, INVERT_BIT);
+
+        let exposed: Vec<f32> = frame
+            .iter()
+            .map(|&pixel| pixel as f32 * exposure * gain)
+            .collect();
+
+        let get_pixel = |x: isize, y: isize| -> f32 {
+            let x = x.clamp(0, SENSOR_WIDTH as isize - 1) as usize;
+            let y = y.clamp(0, SENSOR_HEIGHT as isize - 1) as usize;
+            exposed[y * SENSOR_WIDTH + x]
+        };
+
+        for y in 0..SENSOR_HEIGHT {
+            for x in 0..SENSOR_WIDTH {
+                let (px, py) = (x as isize, y as isize);
+                let center = get_pixel(px, py);
+
+                let mut edge = 0.0;
+                if vh & 0x01 != 0 {
+                    edge += 2.0 * center - get_pixel(px - 1, py) - get_pixel(px + 1, py);
+                }
+                if vh & 0x02 != 0 {
+                    edge += 2.0 * center - get_pixel(px, py - 1) - get_pixel(px, py + 1);
+                }
+
+                let mut value = (center + edge * edge_ratio / 2.0).clamp(0.0, 255.0) as u8;
+                if is_inverted {
+                    value = 255 - value;
+                }
+
+                let colour = self.dither(value, x, y);
+                self.write_pixel(colour, x, y);
+            }
+        }
+    }
+
+    fn dither(&self, value: u8, x: usize, y: usize) -> u8 {
+        let cell = DITHER_MATRIX_START + ((y % 4) * 4 + (x % 4)) * 3;
+        let thresholds = &self.registers[cell..cell + 3];
+
+        // Colour 3 is the darkest
+        if value < thresholds[0] {
+            3
+        } else if value < thresholds[1] {
+            2
+        } else if value < thresholds[2] {
+            1
+        } else {
+            0
+        }
+    }
+
+    fn write_pixel(&mut self, colour: u8, x: usize, y: usize) {
+        let tile = (y / 8) * (SENSOR_WIDTH / 8) + (x / 8);
+        let addr = IMAGE_ADDRESS + tile * 16 + (y % 8) * 2;
+        let bit = 7 - (x % 8);
+
+        for (plane, byte) in self.ram[addr..addr + 2].iter_mut().enumerate() {
+            if (colour >> plane) & 0x01 != 0 {
+                *byte |= 1 << bit;
+            } else {
+                *byte &= !(1 << bit);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn get_camera() -> PocketCamera {
+        let mut rom = vec![0; 0x100000];
+        rom[0x0147] = 0xFC;
+        rom[0x0148] = 0x05;
+        rom[0x0149] = 0x04;
+        let cartridge = Cartridge::from_data("camera.gb".to_string(), rom, &Config::default());
+        PocketCamera::new(&cartridge.unwrap())
+    }
+
+    // Neutral gain and exposure, every dither threshold is the middle grey
+    fn setup_capture(camera: &mut PocketCamera) {
+        camera.write(0x4000, 0x10);
+        camera.write(0xA000 + GAIN_REGISTER as u16, 0x10);
+        camera.write(0xA000 + EXPOSURE_HIGH_REGISTER as u16, 0x08);
+        for register in DITHER_MATRIX_START..REGISTER_COUNT {
+            camera.write(0xA000 + register as u16, 0x80);
+        }
+    }
+
+    #[test]
+    fn capture_takes_time_and_fills_the_image() {
+        let mut camera = get_camera();
+        camera.set_camera_sensor(Box::new(|frame: &mut [u8; SENSOR_PIXELS]| frame.fill(0)));
+        setup_capture(&mut camera);
+
+        camera.write(0xA000, 0x01);
+        let cycles = camera.get_capture_cycles();
+        camera.step(cycles - 1);
+        assert_eq!(camera.read(0xA000), 0x01);
+        camera.step(1);
+        assert_eq!(camera.read(0xA000), 0x00);
+
+        // A black frame is all colour 3
+        let image = &camera.get_save_data()[IMAGE_ADDRESS..IMAGE_ADDRESS + 0xE00];
+        assert!(image.iter().all(|&byte| byte == 0xFF));
+    }
+
+    #[test]
+    fn white_frame_is_colour_0() {
+        let mut camera = get_camera();
+        camera.set_camera_sensor(Box::new(|frame: &mut [u8; SENSOR_PIXELS]| frame.fill(255)));
+        camera.ram.fill(0xFF);
+        setup_capture(&mut camera);
+
+        camera.write(0xA000, 0x01);
+        camera.step(camera.get_capture_cycles());
+        let image = &camera.get_save_data()[IMAGE_ADDRESS..IMAGE_ADDRESS + 0xE00];
+        assert!(image.iter().all(|&byte| byte == 0x00));
+    }
+
+    #[test]
+    fn ram_writes_need_enable_and_skip_registers() {
+        let mut camera = get_camera();
+        camera.write(0xA000, 0x42);
+        assert!(!camera.take_ram_written());
+
+        camera.write(0x0000, 0x0A);
+        camera.write(0x4000, 0x01);
+        camera.write(0xA000, 0x42);
+        assert!(camera.take_ram_written());
+        assert_eq!(camera.read(0xA000), 0x42);
+
+        camera.write(0x4000, 0x10);
+        camera.write(0xA001, 0x42);
+        assert!(!camera.take_ram_written());
+        assert_eq!(camera.read(0xA001), 0x00);
+    }
+}
@@ -0,0 +1,176 @@
+use std::cell::RefCell;
+
+use crate::mappers::infrared::{InfraredPort, InfraredTransport};
+use crate::mappers::mapper::{
+    Mapper, RAM_BANK_SIZE, get_external_ram_size, load_ram, read_rom_bank,
+};
+use crate::rom::cartridge::Cartridge;
+
+// Writing this to 0x0000 - 0x1FFF maps the IR register to 0xA000 - 0xBFFF instead of RAM
+const IR_MODE: u8 = 0x0E;
+
+#[derive(Debug)]
+pub(crate) struct Huc1 {
+    rom_data: Vec<u8>,
+    ram: Vec<u8>,
+    // Set when the game writes to RAM, used to know when to save
+    is_ram_written: bool,
+    is_ir_mode: bool,
+    // 6 bit register
+    rom_bank: u8,
+    ram_bank: u8,
+    // The IR register is read through a RefCell since reading the sensor polls the transport
+    infrared: RefCell<InfraredPort>,
+}
+
+impl Mapper for Huc1 {
+    fn new(cartridge: &Cartridge) -> Self {
+        Huc1 {
+            rom_data: cartridge.get_data().to_vec(),
+            ram: vec![0; get_external_ram_size(cartridge)],
+            is_ram_written: false,
+            is_ir_mode: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            infrared: Default::default(),
+        }
+    }
+
+    fn read(&self, addr: u16) -> u8 {
+        let addr = addr as usize;
+
+        match addr {
+            0x0000..0x4000 => read_rom_bank(&self.rom_data, 0, addr),
+            0x4000..0x8000 => read_rom_bank(&self.rom_data, self.rom_bank as usize, addr - 0x4000),
+            0xA000..0xC000 => {
+                if self.is_ir_mode {
+                    return self.infrared.borrow_mut().read();
+                }
+
+                match self.get_ram_address(addr) {
+                    Some(ram_addr) => self.ram[ram_addr],
+                    None => 0xFF,
+                }
+            }
+            _ => panic!("Out of bank range"),
+        }
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        let addr = addr as usize;
+
+        match addr {
+            0x0000..0x2000 => {
+                // There is no RAM enable, any other value maps RAM
+                self.is_ir_mode = value & 0x0F == IR_MODE;
+            }
+            0x2000..0x4000 => {
+                self.rom_bank = value & 0x3F;
+            }
+            0x4000..0x6000 => {
+                self.ram_bank = value & 0x03;
+            }
+            0x6000..0x8000 => {
+                // No registers here
+            }
+            0xA000..0xC000 => {
+                if self.is_ir_mode {
+                    self.infrared.get_mut().write(value);
+                    return;
+                }
+
+                if let Some(ram_addr) = self.get_ram_address(addr) {
+                    self.ram[ram_addr] = value;
+                    self.is_ram_written = true;
+                }
+            }
+            _ => panic!("Out of bank range"),
+        }
+    }
+
+    fn set_infrared_transport(&mut self, transport: Box<dyn InfraredTransport>) {
+        self.infrared.get_mut().set_transport(transport);
+    }
+
+    fn take_ram_written(&mut self) -> bool {
+        std::mem::take(&mut self.is_ram_written)
+    }
+
+    fn get_save_data(&self) -> Vec<u8> {
+        self.ram.clone()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data);
+    }
+}
+
+impl Huc1 {
+    fn get_ram_address(&self, addr: usize) -> Option<usize> {
+        if self.ram.is_empty() {
+            return None;
+        }
+
+        let ram_addr = self.ram_bank as usize * RAM_BANK_SIZE + (addr - 0xA000);
+        Some(ram_addr % self.ram.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    // The LED shines straight into the sensor
+    struct Loopback(Rc<Cell<bool>>);
+
+    impl InfraredTransport for Loopback {
+        fn set_led(&mut self, is_on: bool) {
+            self.0.set(is_on);
+        }
+
+        fn is_receiving(&mut self) -> bool {
+            self.0.get()
+        }
+    }
+
+    // HuC1 with 32KiB of RAM
+    fn get_huc1() -> Huc1 {
+        let mut rom = vec![0; 0x80000];
+        rom[0x0147] = 0xFF;
+        rom[0x0148] = 0x04;
+        rom[0x0149] = 0x03;
+        let cartridge = Cartridge::from_data("huc1.gb".to_string(), rom, &Config::default());
+        Huc1::new(&cartridge.unwrap())
+    }
+
+    #[test]
+    fn ir_mode_replaces_ram() {
+        let mut huc1 = get_huc1();
+        let led = Rc::new(Cell::new(false));
+        huc1.set_infrared_transport(Box::new(Loopback(led.clone())));
+
+        huc1.write(0xA000, 0x42);
+        assert!(huc1.take_ram_written());
+
+        huc1.write(0x0000, IR_MODE);
+        assert_eq!(huc1.read(0xA000), 0xC0);
+        huc1.write(0xA000, 0x01);
+        assert!(led.get());
+        assert_eq!(huc1.read(0xA000), 0xC1);
+        assert!(!huc1.take_ram_written());
+
+        huc1.write(0x0000, 0x00);
+        assert_eq!(huc1.read(0xA000), 0x42);
+    }
+
+    #[test]
+    fn ram_banks_are_switched() {
+        let mut huc1 = get_huc1();
+        huc1.write(0x4000, 0x02);
+        huc1.write(0xA000, 0x42);
+        assert_eq!(huc1.get_save_data()[2 * RAM_BANK_SIZE], 0x42);
+    }
+}
@@ -0,0 +1,402 @@
+use std::cell::RefCell;
+
+use crate::mappers::infrared::{InfraredPort, InfraredTransport};
+use crate::mappers::mapper::{
+    Mapper, RAM_BANK_SIZE, get_external_ram_size, load_ram, read_rom_bank,
+};
+use crate::mappers::rtc::{RtcClock, RtcMode, get_elapsed, split_footer};
+use crate::mappers::speaker::TonePlayer;
+use crate::rom::cartridge::Cartridge;
+
+// Values written to 0x0000 - 0x1FFF select what is mapped to 0xA000 - 0xBFFF
+const RAM_READ_MODE: u8 = 0x00;
+const RAM_WRITE_MODE: u8 = 0x0A;
+const RTC_COMMAND_MODE: u8 = 0x0B;
+const RTC_RESPONSE_MODE: u8 = 0x0C;
+const RTC_SEMAPHORE_MODE: u8 = 0x0D;
+const IR_MODE: u8 = 0x0E;
+
+// RTC commands, the upper nibble of the command byte
+const READ_COMMAND: u8 = 0x1;
+const WRITE_COMMAND: u8 = 0x3;
+const ADDRESS_LOW_COMMAND: u8 = 0x4;
+const ADDRESS_HIGH_COMMAND: u8 = 0x5;
+const EXTENDED_COMMAND: u8 = 0x6;
+
+// Extended commands, the argument of the extended command
+const LATCH_TIME: u8 = 0x0;
+const SET_TIME: u8 = 0x1;
+const STATUS: u8 = 0x2;
+const PLAY_TONE: u8 = 0xE;
+
+// The clock is copied to and from the first 6 nibbles of memory, minutes then days
+const TIME_NIBBLES: usize = 3;
+const MINUTES_PER_DAY: u16 = 24 * 60;
+// The tone to play is read from this nibble
+const TONE_ADDRESS: usize = 0x26;
+
+// Save footer used by SameBoy and other emulators, packed little endian: a 64 bit UNIX timestamp
+// of when the current minute started, the minutes and days counters, and the alarm
+const FOOTER_SIZE: usize = 17;
+const ALARM_SIZE: usize = 5;
+
+#[derive(Debug)]
+pub(crate) struct Huc3 {
+    rom_data: Vec<u8>,
+    ram: Vec<u8>,
+    // Set when the game writes to RAM, used to know when to save
+    is_ram_written: bool,
+    mode: u8,
+    // 7 bit register
+    rom_bank: u8,
+    ram_bank: u8,
+
+    clock: RtcClock,
+    seconds: u8,
+    // Minutes into the current day
+    minutes: u16,
+    // 12 bit day counter
+    days: u16,
+    // The alarm isn't emulated, it is kept so saves keep it when moved to other emulators
+    alarm: [u8; ALARM_SIZE],
+
+    // The RTC chip is accessed through a 4 bit command and argument, executed through the semaphore
+    command: u8,
+    argument: u8,
+    response: u8,
+    // 256 nibbles of memory inside the RTC chip
+    memory: [u8; 0x100],
+    address: u8,
+    // Where tones from the speaker go, they are dropped if nothing is connected
+    tone_player: Option<Box<dyn TonePlayer>>,
+
+    infrared: RefCell<InfraredPort>,
+}
+
+impl Mapper for Huc3 {
+    fn new(cartridge: &Cartridge) -> Self {
+        Huc3 {
+            rom_data: cartridge.get_data().to_vec(),
+            ram: vec![0; get_external_ram_size(cartridge)],
+            is_ram_written: false,
+            mode: RAM_READ_MODE,
+            rom_bank: 1,
+            ram_bank: 0,
+            clock: RtcClock::new(RtcMode::Emulated),
+            seconds: 0,
+            minutes: 0,
+            days: 0,
+            alarm: [0; ALARM_SIZE],
+            command: 0,
+            argument: 0,
+            response: 0,
+            memory: [0; 0x100],
+            address: 0,
+            tone_player: None,
+            infrared: Default::default(),
+        }
+    }
+
+    fn read(&self, addr: u16) -> u8 {
+        let addr = addr as usize;
+
+        match addr {
+            0x0000..0x4000 => read_rom_bank(&self.rom_data, 0, addr),
+            0x4000..0x8000 => read_rom_bank(&self.rom_data, self.rom_bank as usize, addr - 0x4000),
+            0xA000..0xC000 => match self.mode {
+                RAM_READ_MODE | RAM_WRITE_MODE => match self.get_ram_address(addr) {
+                    Some(ram_addr) => self.ram[ram_addr],
+                    None => 0xFF,
+                },
+                RTC_RESPONSE_MODE => 0x80 | (self.command << 4) | self.response,
+                // Commands complete instantly, so the chip is always ready
+                RTC_SEMAPHORE_MODE => 0xFF,
+                IR_MODE => self.infrared.borrow_mut().read(),
+                _ => 0xFF,
+            },
+            _ => panic!("Out of bank range"),
+        }
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        let addr = addr as usize;
+
+        match addr {
+            0x0000..0x2000 => {
+                self.mode = value & 0x0F;
+            }
+            0x2000..0x4000 => {
+                self.rom_bank = value & 0x7F;
+            }
+            0x4000..0x6000 => {
+                self.ram_bank = value & 0x03;
+            }
+            0x6000..0x8000 => {
+                // No registers here
+            }
+            0xA000..0xC000 => match self.mode {
+                RAM_WRITE_MODE => {
+                    if let Some(ram_addr) = self.get_ram_address(addr) {
+                        self.ram[ram_addr] = value;
+                        self.is_ram_written = true;
+                    }
+                }
+                RTC_COMMAND_MODE => {
+                    self.command = (value >> 4) & 0x07;
+                    self.argument = value & 0x0F;
+                }
+                // Clearing bit 0 executes the pending command
+                RTC_SEMAPHORE_MODE if value & 0x01 == 0 => self.execute_command(),
+                IR_MODE => self.infrared.get_mut().write(value),
+                _ => {}
+            },
+            _ => panic!("Out of bank range"),
+        }
+    }
+
+    fn step(&mut self, cycles: usize) {
+        let seconds = self.clock.step(cycles);
+        self.advance(seconds);
+    }
+
+    fn set_rtc_mode(&mut self, mode: RtcMode) {
+        let seconds = self.clock.set_mode(mode);
+        self.advance(seconds);
+    }
+
+    fn set_infrared_transport(&mut self, transport: Box<dyn InfraredTransport>) {
+        self.infrared.get_mut().set_transport(transport);
+    }
+
+    fn set_tone_player(&mut self, player: Box<dyn TonePlayer>) {
+        self.tone_player = Some(player);
+    }
+
+    fn take_ram_written(&mut self) -> bool {
+        std::mem::take(&mut self.is_ram_written)
+    }
+
+    fn get_save_data(&self) -> Vec<u8> {
+        let mut data = self.ram.clone();
+        data.extend(self.get_footer());
+        data
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        let (ram, footer) = split_footer(data, self.ram.len(), &[FOOTER_SIZE]);
+        load_ram(&mut self.ram, ram);
+        if let Some(footer) = footer {
+            self.load_footer(footer);
+        }
+    }
+}
+
+impl Huc3 {
+    fn get_ram_address(&self, addr: usize) -> Option<usize> {
+        if self.ram.is_empty() {
+            return None;
+        }
+
+        let ram_addr = self.ram_bank as usize * RAM_BANK_SIZE + (addr - 0xA000);
+        Some(ram_addr % self.ram.len())
+    }
+
+    /**
+     * Seconds aren't saved, the timestamp is moved back to the start of the minute instead
+     */
+    fn get_footer(&self) -> Vec<u8> {
+        let timestamp = self
+            .clock
+            .get_timestamp()
+            .saturating_sub(self.seconds as u64);
+
+        let mut footer = timestamp.to_le_bytes().to_vec();
+        footer.extend(self.minutes.to_le_bytes());
+        footer.extend(self.days.to_le_bytes());
+        footer.extend(self.alarm);
+        footer
+    }
+
+    fn load_footer(&mut self, footer: &[u8]) {
+        let timestamp = u64::from_le_bytes(footer[0..8].try_into().unwrap());
+        self.minutes = u16::from_le_bytes([footer[8], footer[9]]) % MINUTES_PER_DAY;
+        self.days = u16::from_le_bytes([footer[10], footer[11]]) & 0xFFF;
+        self.alarm.copy_from_slice(&footer[12..FOOTER_SIZE]);
+        self.seconds = 0;
+        self.advance(get_elapsed(timestamp));
+    }
+
+    fn advance(&mut self, seconds: u64) {
+        let seconds = self.seconds as u64 + seconds;
+        self.seconds = (seconds % 60) as u8;
+
+        let minutes = self.minutes as u64 + seconds / 60;
+        self.minutes = (minutes % MINUTES_PER_DAY as u64) as u16;
+        let days = self.days as u64 + minutes / MINUTES_PER_DAY as u64;
+        self.days = (days & 0xFFF) as u16;
+    }
+
+    fn execute_command(&mut self) {
+        let seconds = self.clock.sync();
+        self.advance(seconds);
+
+        match self.command {
+            READ_COMMAND => {
+                self.response = self.memory[self.address as usize];
+                self.address = self.address.wrapping_add(1);
+            }
+            WRITE_COMMAND => {
+                self.memory[self.address as usize] = self.argument;
+                self.address = self.address.wrapping_add(1);
+            }
+            ADDRESS_LOW_COMMAND => {
+                self.address = (self.address & 0xF0) | self.argument;
+            }
+            ADDRESS_HIGH_COMMAND => {
+                self.address = (self.address & 0x0F) | (self.argument << 4);
+            }
+            EXTENDED_COMMAND => self.execute_extended_command(),
+            _ => {}
+        }
+    }
+
+    fn execute_extended_command(&mut self) {
+        match self.argument {
+            LATCH_TIME => {
+                write_nibbles(&mut self.memory[0..TIME_NIBBLES], self.minutes);
+                write_nibbles(&mut self.memory[TIME_NIBBLES..TIME_NIBBLES * 2], self.days);
+            }
+            SET_TIME => {
+                let minutes = read_nibbles(&self.memory[0..TIME_NIBBLES]);
+                self.minutes = minutes % MINUTES_PER_DAY;
+                self.days = read_nibbles(&self.memory[TIME_NIBBLES..TIME_NIBBLES * 2]);
+                self.seconds = 0;
+                self.clock.reset_sub_second();
+            }
+            STATUS => {
+                // The clock is always running
+                self.response = 0x01;
+            }
+            PLAY_TONE => {
+                if let Some(tone_player) = self.tone_player.as_mut() {
+                    tone_player.play_tone(self.memory[TONE_ADDRESS]);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// Values are stored least significant nibble first
+fn write_nibbles(memory: &mut [u8], value: u16) {
+    for (i, nibble) in memory.iter_mut().enumerate() {
+        *nibble = ((value >> (i * 4)) & 0x0F) as u8;
+    }
+}
+
+fn read_nibbles(memory: &[u8]) -> u16 {
+    memory.iter().enumerate().fold(0, |value, (i, nibble)| {
+        value | ((*nibble as u16) << (i * 4))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use std::rc::Rc;
+
+    // HuC3 with 32KiB of RAM
+    fn huc3_cartridge() -> Cartridge {
+        let mut rom = vec![0; 0x20000];
+        rom[0x0147] = 0xFE;
+        rom[0x0149] = 0x03;
+        Cartridge::from_data("huc3.gb".to_string(), rom, &Config::default()).unwrap()
+    }
+
+    #[test]
+    fn saves_clock_in_sameboy_format() {
+        let mut huc3 = Huc3::new(&huc3_cartridge());
+        huc3.minutes = 0x123;
+        huc3.days = 0x456;
+        huc3.alarm = [1, 2, 3, 4, 5];
+
+        let data = huc3.get_save_data();
+        assert_eq!(data.len(), 0x8000 + FOOTER_SIZE);
+        let footer = &data[0x8000..];
+        assert_eq!(footer[8..12], [0x23, 0x01, 0x56, 0x04]);
+        assert_eq!(footer[12..], [1, 2, 3, 4, 5]);
+
+        let mut loaded = Huc3::new(&huc3_cartridge());
+        loaded.load_save_data(&data);
+        assert_eq!(loaded.minutes, 0x123);
+        assert_eq!(loaded.days, 0x456);
+        assert_eq!(loaded.alarm, [1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn catches_up_on_time_since_save() {
+        let mut footer = (get_unix_time_now() - 2 * 60 * 60).to_le_bytes().to_vec();
+        footer.extend(10u16.to_le_bytes());
+        footer.extend(3u16.to_le_bytes());
+        footer.extend([0; ALARM_SIZE]);
+
+        let mut huc3 = Huc3::new(&huc3_cartridge());
+        huc3.load_save_data(&footer);
+        assert!((130..=131).contains(&huc3.minutes));
+        assert_eq!(huc3.days, 3);
+    }
+
+    #[test]
+    fn latches_time_into_memory() {
+        let mut huc3 = Huc3::new(&huc3_cartridge());
+        huc3.advance(MINUTES_PER_DAY as u64 * 60 + 5 * 60);
+        huc3.argument = LATCH_TIME;
+        huc3.execute_extended_command();
+        assert_eq!(read_nibbles(&huc3.memory[0..TIME_NIBBLES]), 5);
+        assert_eq!(
+            read_nibbles(&huc3.memory[TIME_NIBBLES..TIME_NIBBLES * 2]),
+            1
+        );
+    }
+
+    // Queue a command, then clear the semaphore to run it
+    fn send_command(huc3: &mut Huc3, command: u8, argument: u8) {
+        huc3.write(0x0000, RTC_COMMAND_MODE);
+        huc3.write(0xA000, (command << 4) | argument);
+        huc3.write(0x0000, RTC_SEMAPHORE_MODE);
+        huc3.write(0xA000, 0xFE);
+    }
+
+    struct Speaker(Rc<RefCell<Vec<u8>>>);
+
+    impl TonePlayer for Speaker {
+        fn play_tone(&mut self, tone: u8) {
+            self.0.borrow_mut().push(tone);
+        }
+    }
+
+    #[test]
+    fn plays_tones_from_memory() {
+        let mut huc3 = Huc3::new(&huc3_cartridge());
+        let tones = Rc::new(RefCell::new(Vec::new()));
+        huc3.set_tone_player(Box::new(Speaker(tones.clone())));
+
+        send_command(&mut huc3, ADDRESS_LOW_COMMAND, (TONE_ADDRESS & 0x0F) as u8);
+        send_command(&mut huc3, ADDRESS_HIGH_COMMAND, (TONE_ADDRESS >> 4) as u8);
+        send_command(&mut huc3, WRITE_COMMAND, 0x5);
+        send_command(&mut huc3, EXTENDED_COMMAND, PLAY_TONE);
+        assert_eq!(*tones.borrow(), [0x5]);
+
+        // Setting the semaphore doesn't run the command again
+        huc3.write(0xA000, 0x01);
+        assert_eq!(*tones.borrow(), [0x5]);
+    }
+
+    fn get_unix_time_now() -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+}
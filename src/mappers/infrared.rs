@@ -0,0 +1,47 @@
+use std::fmt;
+
+/**
+ * Connection for cartridges with an infrared LED and sensor, frontends can use this to link two
+ * emulators together or to script IR traffic
+ */
+pub trait InfraredTransport {
+    // Called when the cartridge turns its LED on or off
+    fn set_led(&mut self, is_on: bool);
+    // Whether light is hitting the cartridge's sensor
+    fn is_receiving(&mut self) -> bool;
+}
+
+impl fmt::Debug for dyn InfraredTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("InfraredTransport")
+    }
+}
+
+/**
+ * IR port shared by HuC1 and HuC3, bit 0 is the LED when written and the sensor when read
+ */
+#[derive(Debug, Default)]
+pub(crate) struct InfraredPort {
+    transport: Option<Box<dyn InfraredTransport>>,
+}
+
+impl InfraredPort {
+    pub fn read(&mut self) -> u8 {
+        let is_receiving = self
+            .transport
+            .as_mut()
+            .is_some_and(|transport| transport.is_receiving());
+        // Upper bits always read as 1s
+        0xC0 | is_receiving as u8
+    }
+
+    pub fn write(&mut self, value: u8) {
+        if let Some(transport) = self.transport.as_mut() {
+            transport.set_led(value & 0x01 != 0);
+        }
+    }
+
+    pub fn set_transport(&mut self, transport: Box<dyn InfraredTransport>) {
+        self.transport = Some(transport);
+    }
+}
@@ -0,0 +1,99 @@
+use crate::mappers::mapper::{Mapper, ROM_BANK_SIZE, read_rom_bank};
+use crate::rom::cartridge::Cartridge;
+
+// Mani 4 in 1 is four 32KiB games
+const M161_ROM_SIZE: usize = 0x40000;
+const M161_TITLE: &str = "TETRIS SET";
+
+/**
+ * M161 is used by the Mani 4 in 1 (DMG-601) compilation. The menu selects a 32KiB game with a
+ * single write, after which the bank is locked until reset.
+ */
+#[derive(Debug)]
+pub(crate) struct M161 {
+    rom_data: Vec<u8>,
+    // 32KiB bank
+    rom_bank: u8,
+    is_locked: bool,
+}
+
+impl Mapper for M161 {
+    fn new(cartridge: &Cartridge) -> Self {
+        M161 {
+            rom_data: cartridge.get_data().to_vec(),
+            rom_bank: 0,
+            is_locked: false,
+        }
+    }
+
+    fn read(&self, addr: u16) -> u8 {
+        let addr = addr as usize;
+
+        match addr {
+            0x0000..0x8000 => {
+                let bank = self.rom_bank as usize * 2 + addr / ROM_BANK_SIZE;
+                read_rom_bank(&self.rom_data, bank, addr % ROM_BANK_SIZE)
+            }
+            0xA000..0xC000 => 0xFF,
+            _ => panic!("Out of bank range"),
+        }
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        let addr = addr as usize;
+
+        match addr {
+            0x4000..0x6000 => {
+                if !self.is_locked {
+                    self.rom_bank = value & 0x07;
+                    self.is_locked = true;
+                }
+            }
+            0x0000..0x4000 | 0x6000..0x8000 | 0xA000..0xC000 => {}
+            _ => panic!("Out of bank range"),
+        }
+    }
+}
+
+/**
+ * The header claims MBC3 + RAM + BATTERY, but the menu title and ROM size give it away
+ */
+pub(crate) fn is_m161(cartridge: &Cartridge) -> bool {
+    let header = &cartridge.rom_header;
+    header.cartridge_type == 0x10
+        && header.title.trim_end() == M161_TITLE
+        && cartridge.get_data().len() == M161_ROM_SIZE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::rom::rom_header::TITLE_START;
+
+    // Each 32KiB game has its number at 0x0200
+    fn m161_cartridge() -> Cartridge {
+        let mut rom = vec![0; M161_ROM_SIZE];
+        for game in 0..M161_ROM_SIZE / 0x8000 {
+            rom[game * 0x8000 + 0x0200] = game as u8;
+        }
+        rom[TITLE_START..TITLE_START + M161_TITLE.len()].copy_from_slice(M161_TITLE.as_bytes());
+        rom[0x0147] = 0x10;
+        Cartridge::from_data("m161.gb".to_string(), rom, &Config::default()).unwrap()
+    }
+
+    #[test]
+    fn detects_menu() {
+        assert!(is_m161(&m161_cartridge()));
+    }
+
+    #[test]
+    fn locks_after_first_bank_switch() {
+        let mut m161 = M161::new(&m161_cartridge());
+        m161.write(0x4000, 0x05);
+        assert_eq!(m161.read(0x0200), 5);
+
+        m161.write(0x4000, 0x02);
+        assert_eq!(m161.read(0x0200), 5);
+    }
+}
@@ -1,15 +1,279 @@
+use std::io;
+
+use serde::Deserialize;
+
 use crate::{
-    mappers::{mbc1::Mbc1, no_mbc::NoMbc},
-    rom::cartridge::Cartridge,
+    config::MapperOverride,
+    mappers::{
+        camera::{CameraSensor, PocketCamera},
+        huc1::Huc1,
+        huc3::Huc3,
+        infrared::InfraredTransport,
+        m161::{self, M161},
+        mbc1::Mbc1,
+        mbc2::Mbc2,
+        mbc3::Mbc3,
+        mbc5::Mbc5,
+        mbc7::Mbc7,
+        mmm01::{self, Mmm01},
+        no_mbc::NoMbc,
+        rtc::RtcMode,
+        sachen::{self, Sachen},
+        speaker::TonePlayer,
+        tama5::Tama5,
+        wisdom_tree::{self, WisdomTree},
+    },
+    rom::{cartridge::Cartridge, rom_header::MapperKind},
 };
 
-pub trait Mapper {
-    fn new(cartridge: &Cartridge) -> Self;
+pub(crate) const ROM_BANK_SIZE: usize = 0x4000;
+pub(crate) const RAM_BANK_SIZE: usize = 0x2000;
+
+/**
+ * Mappers with no cartridge type code, they can only be picked by name in the config
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UnlicensedMapper {
+    WisdomTree,
+    M161,
+    Sachen,
+}
+
+pub trait Mapper: std::fmt::Debug {
+    fn new(cartridge: &Cartridge) -> Self
+    where
+        Self: Sized;
+    // Handles both the ROM area (0x0000 - 0x7FFF) and external RAM (0xA000 - 0xBFFF)
     fn read(&self, addr: u16) -> u8;
     fn write(&mut self, addr: u16, value: u8);
+
+    // Contents of battery backed memory, in the raw format other emulators and flash carts use
+    fn get_save_data(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    fn load_save_data(&mut self, _data: &[u8]) {}
+
+    // Whether battery backed memory changed since the last call, register writes don't count
+    fn take_ram_written(&mut self) -> bool {
+        false
+    }
+
+    // Advance any hardware on the cartridge by a number of M-cycles
+    fn step(&mut self, _cycles: usize) {}
+
+    // Called when the game starts without a boot ROM, only used by cartridges that watch it run
+    fn skip_boot(&mut self) {}
+
+    // Only used by cartridges with a real-time clock
+    fn set_rtc_mode(&mut self, _mode: RtcMode) {}
+
+    // Only used by cartridges with a rumble motor
+    fn is_rumbling(&self) -> bool {
+        false
+    }
+
+    // Only used by cartridges with an accelerometer, tilt is in g
+    fn set_tilt(&mut self, _x: f32, _y: f32) {}
+
+    // Only used by cartridges with a camera
+    fn set_camera_sensor(&mut self, _sensor: Box<dyn CameraSensor>) {}
+
+    // Only used by cartridges with an infrared port
+    fn set_infrared_transport(&mut self, _transport: Box<dyn InfraredTransport>) {}
+
+    // Only used by cartridges with a speaker
+    fn set_tone_player(&mut self, _player: Box<dyn TonePlayer>) {}
 }
 
-pub fn get_mapper(cartridge: &Cartridge) -> impl Mapper {
-    // Factory function to get the correct mapper, for now only Mbc1 is implemented
-    Mbc1::new(cartridge)
+/**
+ * Factory function to get the correct mapper based on the cartridge type in the header (0x0147)
+ */
+pub fn get_mapper(cartridge: &Cartridge) -> io::Result<Box<dyn Mapper>> {
+    // A mapper set in the config is trusted over the detection
+    match cartridge.get_config().mapper {
+        Some(MapperOverride::Unlicensed(mapper)) => {
+            return Ok(new_unlicensed_mapper(mapper, cartridge));
+        }
+        Some(MapperOverride::CartridgeType(_)) => {}
+        None => {
+            if let Some(mapper) = detect_unlicensed_mapper(cartridge) {
+                return Ok(mapper);
+            }
+        }
+    }
+
+    let cartridge_type = cartridge.get_cartridge_type()?;
+
+    match cartridge_type.mapper {
+        MapperKind::NoMbc => Ok(Box::new(NoMbc::new(cartridge))),
+        MapperKind::Mbc1 => Ok(Box::new(Mbc1::new(cartridge))),
+        MapperKind::Mbc2 => Ok(Box::new(Mbc2::new(cartridge))),
+        MapperKind::Mmm01 => Ok(Box::new(Mmm01::new(cartridge))),
+        MapperKind::Mbc3 => Ok(Box::new(Mbc3::new(cartridge))),
+        MapperKind::Mbc5 => Ok(Box::new(Mbc5::new(cartridge))),
+        MapperKind::Mbc7 => Ok(Box::new(Mbc7::new(cartridge))),
+        MapperKind::PocketCamera => Ok(Box::new(PocketCamera::new(cartridge))),
+        MapperKind::Tama5 => Ok(Box::new(Tama5::new(cartridge))),
+        MapperKind::Huc3 => Ok(Box::new(Huc3::new(cartridge))),
+        MapperKind::Huc1 => Ok(Box::new(Huc1::new(cartridge))),
+        MapperKind::Mbc6 => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("Unsupported mapper {:?}", cartridge_type.mapper),
+        )),
+    }
+}
+
+/**
+ * Unlicensed carts often have headers that lie about the mapper, so they are detected from
+ * their ROM size and quirks in the header instead. There is no list of known dumps, carts the
+ * heuristics miss can have the mapper named in the config.
+ */
+fn detect_unlicensed_mapper(cartridge: &Cartridge) -> Option<Box<dyn Mapper>> {
+    if sachen::is_sachen(cartridge) {
+        return Some(Box::new(Sachen::new(cartridge)));
+    }
+
+    if wisdom_tree::is_wisdom_tree(cartridge) {
+        return Some(Box::new(WisdomTree::new(cartridge)));
+    }
+
+    if m161::is_m161(cartridge) {
+        return Some(Box::new(M161::new(cartridge)));
+    }
+
+    // MMM01 carts start with the first game's header, a mapper named there is trusted
+    if !has_supported_mapper(cartridge) && mmm01::is_mmm01(cartridge.get_data()) {
+        return Some(Box::new(Mmm01::new(cartridge)));
+    }
+
+    None
+}
+
+fn new_unlicensed_mapper(mapper: UnlicensedMapper, cartridge: &Cartridge) -> Box<dyn Mapper> {
+    match mapper {
+        UnlicensedMapper::WisdomTree => Box::new(WisdomTree::new(cartridge)),
+        UnlicensedMapper::M161 => Box::new(M161::new(cartridge)),
+        UnlicensedMapper::Sachen => Box::new(Sachen::new(cartridge)),
+    }
+}
+
+/**
+ * Whether the cartridge type in the header (0x0147) has a mapper that can be emulated
+ */
+fn has_supported_mapper(cartridge: &Cartridge) -> bool {
+    cartridge
+        .rom_header
+        .get_cartridge_type()
+        .is_ok_and(|cartridge_type| cartridge_type.mapper != MapperKind::Mbc6)
+}
+
+/**
+ * Cartridge types with a battery keep their RAM when the power is off
+ */
+pub(crate) fn has_battery(cartridge: &Cartridge) -> bool {
+    cartridge
+        .get_cartridge_type()
+        .is_ok_and(|cartridge_type| cartridge_type.has_battery)
+}
+
+/**
+ * Copy a save into RAM, saves of the wrong size are truncated or padded
+ */
+pub(crate) fn load_ram(ram: &mut [u8], data: &[u8]) {
+    let length = ram.len().min(data.len());
+    ram[..length].copy_from_slice(&data[..length]);
+}
+
+/**
+ * Get the size of the external RAM on the cartridge in bytes (0x0149), unknown sizes are treated
+ * as no RAM since bootleg carts often have garbage headers
+ */
+pub(crate) fn get_external_ram_size(cartridge: &Cartridge) -> usize {
+    cartridge
+        .rom_header
+        .get_ram_size()
+        .map_or(0, |ram_size| ram_size.get_size())
+}
+
+/**
+ * Read from a 16KiB ROM bank, bank numbers wrap around to the number of banks on the cartridge
+ */
+pub(crate) fn read_rom_bank(rom_data: &[u8], bank: usize, offset: usize) -> u8 {
+    let bank_count = (rom_data.len() / ROM_BANK_SIZE).max(1);
+    let bank = bank % bank_count;
+    rom_data
+        .get(bank * ROM_BANK_SIZE + offset)
+        .copied()
+        .unwrap_or(0xFF)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::rom::rom_header::{HEADER_CHECKSUM_ADDR, LOGO_START, NINTENDO_LOGO};
+    use crate::rom::validation::compute_header_checksum;
+
+    // Each 16KiB bank starts with its bank number
+    fn get_cartridge(cartridge_type: u8) -> Cartridge {
+        let mut rom = vec![0; 0x10000];
+        for bank in 0..4 {
+            rom[bank * ROM_BANK_SIZE] = bank as u8;
+        }
+        rom[0x0147] = cartridge_type;
+        rom[0x0148] = 0x01;
+        Cartridge::from_data("mapper.gb".to_string(), rom, &Config::default()).unwrap()
+    }
+
+    #[test]
+    fn mapper_comes_from_cartridge_type() {
+        // MBC1 switches the bank at 0x4000, without a mapper the ROM is fixed
+        let mut mapper = get_mapper(&get_cartridge(0x01)).unwrap();
+        mapper.write(0x2000, 0x02);
+        assert_eq!(mapper.read(0x4000), 2);
+
+        let mut mapper = get_mapper(&get_cartridge(0x00)).unwrap();
+        mapper.write(0x2000, 0x02);
+        assert_eq!(mapper.read(0x4000), 1);
+    }
+
+    #[test]
+    fn mbc_games_are_not_mmm01() {
+        // A normal game with the MMM01 type where the menu header would be
+        let mut rom = get_cartridge(0x01).get_data().to_vec();
+        rom[0x8000 + 0x0147] = 0x0B;
+        let cartridge = Cartridge::from_data("mapper.gb".to_string(), rom, &Config::default());
+        let mapper = get_mapper(&cartridge.unwrap()).unwrap();
+        assert_eq!(mapper.read(0x0000), 0);
+        assert_eq!(mapper.read(0x4000), 1);
+    }
+
+    #[test]
+    fn detects_mmm01_menu() {
+        // The first game's header has an unknown type, the menu in the last 32KiB is MMM01
+        let mut rom = get_cartridge(0x04).get_data().to_vec();
+        let menu = &mut rom[0x8000..];
+        menu[LOGO_START..LOGO_START + NINTENDO_LOGO.len()].copy_from_slice(&NINTENDO_LOGO);
+        menu[0x0147] = 0x0B;
+        menu[HEADER_CHECKSUM_ADDR] = compute_header_checksum(menu);
+        let cartridge = Cartridge::from_data("mapper.gb".to_string(), rom, &Config::default());
+        let mapper = get_mapper(&cartridge.unwrap()).unwrap();
+        assert_eq!(mapper.read(0x0000), 2);
+    }
+
+    #[test]
+    fn unsupported_mappers_are_errors() {
+        let error = get_mapper(&get_cartridge(0x20)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn rom_banks_wrap_around() {
+        let rom = [1, 2, 3];
+        assert_eq!(read_rom_bank(&rom, 0, 1), 2);
+        assert_eq!(read_rom_bank(&rom, 5, 2), 3);
+        assert_eq!(read_rom_bank(&rom, 0, 3), 0xFF);
+    }
 }
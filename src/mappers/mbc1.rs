@@ -1,17 +1,43 @@
-use crate::mappers::mapper::Mapper;
+use crate::mappers::mapper::{
+    Mapper, RAM_BANK_SIZE, ROM_BANK_SIZE, get_external_ram_size, load_ram, read_rom_bank,
+};
 use crate::rom::cartridge::Cartridge;
+use crate::rom::rom_header::{LOGO_START, NINTENDO_LOGO};
+
+// MBC1M multicarts are 1 MiB, each game takes 16 banks and has its own header
+const MULTICART_ROM_SIZE: usize = 0x100000;
+const MULTICART_GAME_BANKS: usize = 0x10;
 
 #[derive(Debug)]
 pub(crate) struct Mbc1 {
     rom_data: Vec<u8>,
+    ram: Vec<u8>,
+    // Set when the game writes to RAM, used to know when to save
+    is_ram_written: bool,
     is_ram_enabled: bool,
+    // 5 bit register selecting the ROM bank for 0x4000 - 0x7FFF
+    rom_bank: u8,
+    // 2 bit register, either the RAM bank or the upper bits of the ROM bank
+    secondary_bank: u8,
+    // false is simple banking mode, true is advanced banking mode
+    is_advanced_banking_mode: bool,
+    // MBC1M wires the secondary register to bits 4-5 of the ROM bank instead of 5-6
+    is_multicart: bool,
 }
 
 impl Mapper for Mbc1 {
     fn new(cartridge: &Cartridge) -> Self {
+        let rom_data = cartridge.get_data();
+
         Mbc1 {
-            rom_data: cartridge.get_data().to_vec(),
+            is_multicart: is_multicart(rom_data),
+            rom_data: rom_data.to_vec(),
+            ram: vec![0; get_external_ram_size(cartridge)],
+            is_ram_written: false,
             is_ram_enabled: false,
+            rom_bank: 1,
+            secondary_bank: 0,
+            is_advanced_banking_mode: false,
         }
     }
 
@@ -19,15 +45,24 @@ impl Mapper for Mbc1 {
         let addr = addr as usize;
 
         match addr {
-            0x0000..0x4000 => self.rom_data[addr],
-            // TODO: Rom banking here
-            0x4000..0x8000 => self.rom_data[addr],
-            0xA000..0xC000 => {
-                if self.is_ram_enabled {
-                    unimplemented!("RAM is not implemented")
+            0x0000..0x4000 => {
+                // In advanced banking mode the secondary register also applies to the first bank
+                let bank = if self.is_advanced_banking_mode {
+                    self.get_upper_rom_bank()
                 } else {
-                    0xFF
+                    0
+                };
+                self.read_rom(bank, addr)
+            }
+            0x4000..0x8000 => {
+                let bank = self.get_upper_rom_bank() | self.get_lower_rom_bank();
+                self.read_rom(bank, addr - 0x4000)
+            }
+            0xA000..0xC000 => {
+                if !self.is_ram_enabled || self.ram.is_empty() {
+                    return 0xFF;
                 }
+                self.ram[self.get_ram_address(addr)]
             }
             _ => panic!("Out of bank range"),
         }
@@ -42,18 +77,161 @@ impl Mapper for Mbc1 {
                 self.is_ram_enabled = lower_nibble == 0x0A;
             }
             0x2000..0x4000 => {
-                unimplemented!("ROM bank is unimplemented");
+                // Only the lower 5 bits are used, selecting bank 0 actually selects bank 1.
+                // The check is done on the 5 bit value, so banks 0x20, 0x40 and 0x60 can't be
+                // reached through 0x4000 - 0x7FFF either.
+                let bank = value & 0x1F;
+                self.rom_bank = if bank == 0 { 1 } else { bank };
             }
             0x4000..0x6000 => {
-                if self.is_ram_enabled {
-                } else {
-                    0xFF;
-                }
+                self.secondary_bank = value & 0x03;
             }
             0x6000..0x8000 => {
-                unimplemented!("Bank mode select is unimplemented");
+                self.is_advanced_banking_mode = value & 0x01 == 1;
+            }
+            0xA000..0xC000 => {
+                if !self.is_ram_enabled || self.ram.is_empty() {
+                    return;
+                }
+                let ram_addr = self.get_ram_address(addr);
+                self.ram[ram_addr] = value;
+                self.is_ram_written = true;
             }
             _ => panic!("out of bank range"),
         }
     }
+
+    fn take_ram_written(&mut self) -> bool {
+        std::mem::take(&mut self.is_ram_written)
+    }
+
+    fn get_save_data(&self) -> Vec<u8> {
+        self.ram.clone()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data);
+    }
+}
+
+impl Mbc1 {
+    fn get_upper_rom_bank(&self) -> usize {
+        let shift = if self.is_multicart { 4 } else { 5 };
+        (self.secondary_bank as usize) << shift
+    }
+
+    fn get_lower_rom_bank(&self) -> usize {
+        // The bank 0 to 1 check still looks at all 5 bits, but multicarts don't wire up bit 4
+        let mask = if self.is_multicart { 0x0F } else { 0x1F };
+        (self.rom_bank & mask) as usize
+    }
+
+    fn read_rom(&self, bank: usize, offset: usize) -> u8 {
+        read_rom_bank(&self.rom_data, bank, offset)
+    }
+
+    fn get_ram_address(&self, addr: usize) -> usize {
+        // Only 32KiB RAM cartridges can switch RAM banks, and only in advanced banking mode
+        let bank = if self.is_advanced_banking_mode {
+            self.secondary_bank as usize
+        } else {
+            0
+        };
+        (bank * RAM_BANK_SIZE + (addr - 0xA000)) % self.ram.len()
+    }
+}
+
+/**
+ * Multicarts can't be told apart by the header, but each game in the compilation has its own
+ * header, so the Nintendo logo is repeated at the start of the second game (0x40104)
+ */
+fn is_multicart(rom_data: &[u8]) -> bool {
+    if rom_data.len() != MULTICART_ROM_SIZE {
+        return false;
+    }
+
+    let logo_start = MULTICART_GAME_BANKS * ROM_BANK_SIZE + LOGO_START;
+    rom_data[logo_start..logo_start + NINTENDO_LOGO.len()] == NINTENDO_LOGO
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    // MBC1 + RAM + BATTERY with 32KiB of RAM, each bank starts with its bank number
+    fn get_rom(rom_size: usize) -> Vec<u8> {
+        let mut rom = vec![0; rom_size];
+        for bank in 0..rom_size / ROM_BANK_SIZE {
+            rom[bank * ROM_BANK_SIZE] = bank as u8;
+        }
+        rom[0x0147] = 0x03;
+        rom[0x0148] = (rom_size / 0x8000).trailing_zeros() as u8;
+        rom[0x0149] = 0x03;
+        rom
+    }
+
+    fn get_mbc1(rom: Vec<u8>) -> Mbc1 {
+        let cartridge = Cartridge::from_data("mbc1.gb".to_string(), rom, &Config::default());
+        Mbc1::new(&cartridge.unwrap())
+    }
+
+    #[test]
+    fn bank_zero_selects_bank_one() {
+        let mut mbc1 = get_mbc1(get_rom(0x80000));
+        mbc1.write(0x2000, 0x00);
+        assert_eq!(mbc1.read(0x4000), 1);
+        mbc1.write(0x2000, 0x05);
+        assert_eq!(mbc1.read(0x4000), 5);
+        // Only 5 bits are used
+        mbc1.write(0x2000, 0x25);
+        assert_eq!(mbc1.read(0x4000), 5);
+    }
+
+    #[test]
+    fn secondary_bank_selects_upper_rom_bits() {
+        let mut mbc1 = get_mbc1(get_rom(0x200000));
+        mbc1.write(0x4000, 0x01);
+        mbc1.write(0x2000, 0x00);
+        assert_eq!(mbc1.read(0x4000), 0x21);
+        // Simple banking mode always maps bank 0 at 0x0000
+        assert_eq!(mbc1.read(0x0000), 0x00);
+
+        mbc1.write(0x6000, 0x01);
+        assert_eq!(mbc1.read(0x0000), 0x20);
+    }
+
+    #[test]
+    fn ram_banks_need_advanced_mode() {
+        let mut mbc1 = get_mbc1(get_rom(0x80000));
+        assert_eq!(mbc1.read(0xA000), 0xFF);
+        mbc1.write(0x0000, 0x0A);
+
+        mbc1.write(0x4000, 0x02);
+        mbc1.write(0xA000, 0x11);
+        mbc1.write(0x6000, 0x01);
+        mbc1.write(0xA000, 0x22);
+        assert_eq!(mbc1.read(0xA000), 0x22);
+
+        mbc1.write(0x6000, 0x00);
+        assert_eq!(mbc1.read(0xA000), 0x11);
+        assert_eq!(mbc1.get_save_data()[2 * RAM_BANK_SIZE], 0x22);
+    }
+
+    #[test]
+    fn multicarts_use_four_bank_bits() {
+        let mut rom = get_rom(MULTICART_ROM_SIZE);
+        assert!(!is_multicart(&rom));
+        let logo_start = MULTICART_GAME_BANKS * ROM_BANK_SIZE + LOGO_START;
+        rom[logo_start..logo_start + NINTENDO_LOGO.len()].copy_from_slice(&NINTENDO_LOGO);
+        assert!(is_multicart(&rom));
+        let mut mbc1 = get_mbc1(rom);
+
+        // The secondary register selects the game, bit 4 of the ROM bank isn't wired
+        mbc1.write(0x4000, 0x01);
+        mbc1.write(0x2000, 0x12);
+        assert_eq!(mbc1.read(0x4000), 0x12);
+        mbc1.write(0x6000, 0x01);
+        assert_eq!(mbc1.read(0x0000), 0x10);
+    }
 }
@@ -0,0 +1,146 @@
+use crate::mappers::mapper::{Mapper, load_ram, read_rom_bank};
+use crate::rom::cartridge::Cartridge;
+
+// 512 half-bytes of RAM are built into the MBC2 chip
+const RAM_SIZE: usize = 0x200;
+
+#[derive(Debug)]
+pub(crate) struct Mbc2 {
+    rom_data: Vec<u8>,
+    ram: [u8; RAM_SIZE],
+    // Set when the game writes to RAM, used to know when to save
+    is_ram_written: bool,
+    is_ram_enabled: bool,
+    // 4 bit register, up to 16 ROM banks
+    rom_bank: u8,
+}
+
+impl Mapper for Mbc2 {
+    fn new(cartridge: &Cartridge) -> Self {
+        Mbc2 {
+            rom_data: cartridge.get_data().to_vec(),
+            ram: [0; RAM_SIZE],
+            is_ram_written: false,
+            is_ram_enabled: false,
+            rom_bank: 1,
+        }
+    }
+
+    fn read(&self, addr: u16) -> u8 {
+        let addr = addr as usize;
+
+        match addr {
+            0x0000..0x4000 => read_rom_bank(&self.rom_data, 0, addr),
+            0x4000..0x8000 => read_rom_bank(&self.rom_data, self.rom_bank as usize, addr - 0x4000),
+            0xA000..0xC000 => {
+                if !self.is_ram_enabled {
+                    return 0xFF;
+                }
+                // Only the lower nibble is stored, the upper nibble is undefined and reads as 1s
+                self.ram[Self::get_ram_address(addr)] | 0xF0
+            }
+            _ => panic!("Out of bank range"),
+        }
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        let addr = addr as usize;
+
+        match addr {
+            0x0000..0x4000 => {
+                // Bit 8 of the address selects the register being written to
+                if addr & 0x0100 == 0 {
+                    // Enable ram is the lower nibble is $A, any other value disables the ram
+                    self.is_ram_enabled = value & 0x0F == 0x0A;
+                } else {
+                    let bank = value & 0x0F;
+                    self.rom_bank = if bank == 0 { 1 } else { bank };
+                }
+            }
+            0x4000..0x8000 => {
+                // No registers here
+            }
+            0xA000..0xC000 => {
+                if self.is_ram_enabled {
+                    self.ram[Self::get_ram_address(addr)] = value & 0x0F;
+                    self.is_ram_written = true;
+                }
+            }
+            _ => panic!("Out of bank range"),
+        }
+    }
+
+    fn take_ram_written(&mut self) -> bool {
+        std::mem::take(&mut self.is_ram_written)
+    }
+
+    fn get_save_data(&self) -> Vec<u8> {
+        self.ram.to_vec()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data);
+        // Only the lower nibble exists, other emulators may store the upper nibble as 1s
+        for byte in self.ram.iter_mut() {
+            *byte &= 0x0F;
+        }
+    }
+}
+
+impl Mbc2 {
+    fn get_ram_address(addr: usize) -> usize {
+        // Only the bottom 9 bits of the address are used, so the RAM is mirrored across 0xA000 - 0xBFFF
+        (addr - 0xA000) % RAM_SIZE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::mappers::mapper::ROM_BANK_SIZE;
+
+    // MBC2 + BATTERY with 256KiB of ROM, each bank starts with its bank number
+    fn get_mbc2() -> Mbc2 {
+        let mut rom = vec![0; 0x40000];
+        for bank in 0..16 {
+            rom[bank * ROM_BANK_SIZE] = bank as u8;
+        }
+        rom[0x0147] = 0x06;
+        rom[0x0148] = 0x03;
+        let cartridge = Cartridge::from_data("mbc2.gb".to_string(), rom, &Config::default());
+        Mbc2::new(&cartridge.unwrap())
+    }
+
+    #[test]
+    fn address_bit_8_selects_the_register() {
+        let mut mbc2 = get_mbc2();
+        mbc2.write(0x2100, 0x03);
+        assert_eq!(mbc2.read(0x4000), 3);
+        mbc2.write(0x0100, 0x00);
+        assert_eq!(mbc2.read(0x4000), 1);
+
+        // Writing the bank to the RAM enable register does nothing
+        mbc2.write(0x0000, 0x05);
+        assert_eq!(mbc2.read(0x4000), 1);
+        mbc2.write(0x0000, 0x0A);
+        assert_eq!(mbc2.read(0xA000), 0xF0);
+    }
+
+    #[test]
+    fn ram_is_half_bytes_and_mirrored() {
+        let mut mbc2 = get_mbc2();
+        mbc2.write(0x0000, 0x0A);
+        mbc2.write(0xA001, 0xAB);
+        assert_eq!(mbc2.read(0xA001), 0xFB);
+        assert_eq!(mbc2.read(0xA201), 0xFB);
+        assert_eq!(mbc2.get_save_data()[1], 0x0B);
+    }
+
+    #[test]
+    fn loaded_saves_drop_the_upper_nibble() {
+        let mut mbc2 = get_mbc2();
+        mbc2.load_save_data(&[0xF7; RAM_SIZE]);
+        assert_eq!(mbc2.get_save_data(), vec![0x07; RAM_SIZE]);
+    }
+}
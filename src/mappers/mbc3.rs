@@ -0,0 +1,282 @@
+use crate::mappers::mapper::{
+    Mapper, RAM_BANK_SIZE, get_external_ram_size, load_ram, read_rom_bank,
+};
+use crate::mappers::rtc::{Rtc, RtcFooter, RtcMode};
+use crate::rom::cartridge::Cartridge;
+
+// MBC3 tops out at 2 MiB of ROM and 32KiB of RAM, anything bigger needs MBC30
+const MBC3_MAX_ROM_SIZE: usize = 0x200000;
+const MBC3_MAX_RAM_SIZE: usize = 0x8000;
+
+#[derive(Debug)]
+pub(crate) struct Mbc3 {
+    rom_data: Vec<u8>,
+    ram: Vec<u8>,
+    // Set when the game writes to RAM, used to know when to save
+    is_ram_written: bool,
+    // Enables both RAM and the RTC registers
+    is_ram_enabled: bool,
+    // 7 bit register selecting the ROM bank for 0x4000 - 0x7FFF, 8 bits on MBC30
+    rom_bank: u8,
+    // 0x00 - 0x03 selects a RAM bank (0x00 - 0x07 on MBC30), 0x08 - 0x0C selects a RTC register
+    ram_bank: u8,
+    // MBC30 is used by the Japanese Pokemon Crystal, it has 4 MiB of ROM and 64KiB of RAM
+    is_mbc30: bool,
+    // Only MBC3 + TIMER cartridges have a clock
+    rtc: Option<Rtc>,
+    // Last value written to the latch register, the clock is latched on a 0x00 -> 0x01 write
+    latch_register: u8,
+}
+
+impl Mapper for Mbc3 {
+    fn new(cartridge: &Cartridge) -> Self {
+        let has_rtc = cartridge
+            .get_cartridge_type()
+            .is_ok_and(|cartridge_type| cartridge_type.has_timer);
+        let rom_size = cartridge
+            .rom_header
+            .get_rom_size()
+            .map_or(0, |rom_size| rom_size.get_size());
+        let ram_size = get_external_ram_size(cartridge);
+
+        Mbc3 {
+            rom_data: cartridge.get_data().to_vec(),
+            ram: vec![0; ram_size],
+            is_ram_written: false,
+            is_ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            is_mbc30: rom_size > MBC3_MAX_ROM_SIZE || ram_size > MBC3_MAX_RAM_SIZE,
+            rtc: has_rtc.then(|| Rtc::new(RtcMode::Emulated)),
+            latch_register: 0xFF,
+        }
+    }
+
+    fn read(&self, addr: u16) -> u8 {
+        let addr = addr as usize;
+
+        match addr {
+            0x0000..0x4000 => read_rom_bank(&self.rom_data, 0, addr),
+            0x4000..0x8000 => read_rom_bank(&self.rom_data, self.rom_bank as usize, addr - 0x4000),
+            0xA000..0xC000 => {
+                if !self.is_ram_enabled {
+                    return 0xFF;
+                }
+
+                match self.ram_bank {
+                    0x00..=0x07 => match self.get_ram_address(addr) {
+                        Some(ram_addr) => self.ram[ram_addr],
+                        None => 0xFF,
+                    },
+                    0x08..=0x0C => match &self.rtc {
+                        Some(rtc) => rtc.read(self.ram_bank),
+                        None => 0xFF,
+                    },
+                    _ => 0xFF,
+                }
+            }
+            _ => panic!("Out of bank range"),
+        }
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        let addr = addr as usize;
+
+        match addr {
+            0x0000..0x2000 => {
+                // Enable ram is the lower nibble is $A, any other value disables the ram
+                self.is_ram_enabled = value & 0x0F == 0x0A;
+            }
+            0x2000..0x4000 => {
+                let bank = if self.is_mbc30 { value } else { value & 0x7F };
+                self.rom_bank = if bank == 0 { 1 } else { bank };
+            }
+            0x4000..0x6000 => {
+                self.ram_bank = value;
+            }
+            0x6000..0x8000 => {
+                if self.latch_register == 0x00
+                    && value == 0x01
+                    && let Some(rtc) = self.rtc.as_mut()
+                {
+                    rtc.latch();
+                }
+                self.latch_register = value;
+            }
+            0xA000..0xC000 => {
+                if !self.is_ram_enabled {
+                    return;
+                }
+
+                match self.ram_bank {
+                    0x00..=0x07 => {
+                        if let Some(ram_addr) = self.get_ram_address(addr) {
+                            self.ram[ram_addr] = value;
+                            self.is_ram_written = true;
+                        }
+                    }
+                    0x08..=0x0C => {
+                        if let Some(rtc) = self.rtc.as_mut() {
+                            rtc.write(self.ram_bank, value);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            _ => panic!("Out of bank range"),
+        }
+    }
+
+    fn step(&mut self, cycles: usize) {
+        if let Some(rtc) = self.rtc.as_mut() {
+            rtc.step(cycles);
+        }
+    }
+
+    fn set_rtc_mode(&mut self, mode: RtcMode) {
+        if let Some(rtc) = self.rtc.as_mut() {
+            rtc.set_mode(mode);
+        }
+    }
+
+    fn take_ram_written(&mut self) -> bool {
+        std::mem::take(&mut self.is_ram_written)
+    }
+
+    fn get_save_data(&self) -> Vec<u8> {
+        let mut data = self.ram.clone();
+        if let Some(rtc) = &self.rtc {
+            data.extend(rtc.get_footer().to_bytes());
+        }
+        data
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        let (ram, footer) = RtcFooter::split(data, self.ram.len());
+        load_ram(&mut self.ram, ram);
+        if let (Some(rtc), Some(footer)) = (self.rtc.as_mut(), footer) {
+            rtc.load_footer(footer);
+        }
+    }
+}
+
+impl Mbc3 {
+    fn get_ram_address(&self, addr: usize) -> Option<usize> {
+        if self.ram.is_empty() {
+            return None;
+        }
+
+        // Only MBC30 wires up the third RAM bank bit
+        let bank_mask = if self.is_mbc30 { 0x07 } else { 0x03 };
+        let bank = (self.ram_bank & bank_mask) as usize;
+        let ram_addr = bank * RAM_BANK_SIZE + (addr - 0xA000);
+        Some(ram_addr % self.ram.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    // MBC3 + TIMER + RAM + BATTERY with 32KiB of RAM
+    fn mbc3_cartridge() -> Cartridge {
+        let mut rom = vec![0; 0x20000];
+        rom[0x0147] = 0x10;
+        rom[0x0148] = 0x02;
+        rom[0x0149] = 0x03;
+        Cartridge::from_data("mbc3.gb".to_string(), rom, &Config::default()).unwrap()
+    }
+
+    #[test]
+    fn only_ram_writes_mark_ram_written() {
+        let mut mbc3 = Mbc3::new(&mbc3_cartridge());
+        mbc3.write(0x0000, 0x0A);
+
+        // Selecting and setting a clock register doesn't change the RAM
+        mbc3.write(0x4000, 0x08);
+        mbc3.write(0xA000, 0x12);
+        assert!(!mbc3.take_ram_written());
+
+        mbc3.write(0x4000, 0x01);
+        mbc3.write(0xA000, 0x34);
+        assert!(mbc3.take_ram_written());
+        assert!(!mbc3.take_ram_written());
+    }
+
+    #[test]
+    fn disabled_ram_is_not_written() {
+        let mut mbc3 = Mbc3::new(&mbc3_cartridge());
+        mbc3.write(0xA000, 0x34);
+        assert!(!mbc3.take_ram_written());
+        assert_eq!(mbc3.read(0xA000), 0xFF);
+    }
+
+    #[test]
+    fn clock_reads_come_from_the_latch() {
+        let mut mbc3 = Mbc3::new(&mbc3_cartridge());
+        mbc3.write(0x0000, 0x0A);
+        mbc3.write(0x4000, 0x08);
+
+        // A second of M-cycles
+        mbc3.step(1_048_576);
+        assert_eq!(mbc3.read(0xA000), 0xC0);
+
+        mbc3.write(0x6000, 0x00);
+        mbc3.write(0x6000, 0x01);
+        assert_eq!(mbc3.read(0xA000), 0xC1);
+
+        // Only a 0x00 -> 0x01 write latches the clock
+        mbc3.step(1_048_576);
+        mbc3.write(0x6000, 0x01);
+        assert_eq!(mbc3.read(0xA000), 0xC1);
+    }
+
+    #[test]
+    fn cartridges_without_timer_have_no_clock() {
+        let mut rom = vec![0; 0x20000];
+        rom[0x0147] = 0x13;
+        rom[0x0148] = 0x02;
+        rom[0x0149] = 0x03;
+        let cartridge = Cartridge::from_data("mbc3.gb".to_string(), rom, &Config::default());
+        let mut mbc3 = Mbc3::new(&cartridge.unwrap());
+        mbc3.write(0x0000, 0x0A);
+        mbc3.write(0x4000, 0x08);
+        assert_eq!(mbc3.read(0xA000), 0xFF);
+        assert_eq!(mbc3.get_save_data().len(), 0x8000);
+    }
+
+    #[test]
+    fn mbc30_uses_all_bank_bits() {
+        // 4 MiB of ROM and 64KiB of RAM, each bank starts with its bank number
+        let mut rom = vec![0; 0x400000];
+        for bank in 0..0x100 {
+            rom[bank * 0x4000] = bank as u8;
+        }
+        rom[0x0147] = 0x13;
+        rom[0x0148] = 0x07;
+        rom[0x0149] = 0x05;
+        let cartridge = Cartridge::from_data("mbc30.gb".to_string(), rom, &Config::default());
+        let mut mbc30 = Mbc3::new(&cartridge.unwrap());
+        assert!(mbc30.is_mbc30);
+
+        mbc30.write(0x2000, 0x85);
+        assert_eq!(mbc30.read(0x4000), 0x85);
+
+        mbc30.write(0x0000, 0x0A);
+        mbc30.write(0x4000, 0x07);
+        mbc30.write(0xA000, 0x42);
+        assert_eq!(mbc30.get_save_data()[7 * RAM_BANK_SIZE], 0x42);
+    }
+
+    #[test]
+    fn mbc3_masks_bank_bits() {
+        let mut mbc3 = Mbc3::new(&mbc3_cartridge());
+        assert!(!mbc3.is_mbc30);
+
+        mbc3.write(0x0000, 0x0A);
+        mbc3.write(0x4000, 0x05);
+        mbc3.write(0xA000, 0x42);
+        assert_eq!(mbc3.get_save_data()[RAM_BANK_SIZE], 0x42);
+    }
+}
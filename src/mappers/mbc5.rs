@@ -0,0 +1,176 @@
+use crate::mappers::mapper::{
+    Mapper, RAM_BANK_SIZE, get_external_ram_size, load_ram, read_rom_bank,
+};
+use crate::rom::cartridge::Cartridge;
+
+// Bit 3 of the RAM bank register drives the motor on rumble cartridges
+const RUMBLE_BIT: u8 = 3;
+
+#[derive(Debug)]
+pub(crate) struct Mbc5 {
+    rom_data: Vec<u8>,
+    ram: Vec<u8>,
+    // Set when the game writes to RAM, used to know when to save
+    is_ram_written: bool,
+    is_ram_enabled: bool,
+    // 9 bit register, unlike the other MBCs bank 0 can be mapped to 0x4000 - 0x7FFF
+    rom_bank: u16,
+    // 4 bit register, rumble cartridges only have 3 bits
+    ram_bank: u8,
+    has_rumble: bool,
+    is_rumbling: bool,
+}
+
+impl Mapper for Mbc5 {
+    fn new(cartridge: &Cartridge) -> Self {
+        let has_rumble = cartridge
+            .get_cartridge_type()
+            .is_ok_and(|cartridge_type| cartridge_type.has_rumble);
+
+        Mbc5 {
+            rom_data: cartridge.get_data().to_vec(),
+            ram: vec![0; get_external_ram_size(cartridge)],
+            is_ram_written: false,
+            is_ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            has_rumble,
+            is_rumbling: false,
+        }
+    }
+
+    fn read(&self, addr: u16) -> u8 {
+        let addr = addr as usize;
+
+        match addr {
+            0x0000..0x4000 => read_rom_bank(&self.rom_data, 0, addr),
+            0x4000..0x8000 => read_rom_bank(&self.rom_data, self.rom_bank as usize, addr - 0x4000),
+            0xA000..0xC000 => match self.get_ram_address(addr) {
+                Some(ram_addr) => self.ram[ram_addr],
+                None => 0xFF,
+            },
+            _ => panic!("Out of bank range"),
+        }
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        let addr = addr as usize;
+
+        match addr {
+            0x0000..0x2000 => {
+                // Enable ram is the lower nibble is $A, any other value disables the ram
+                self.is_ram_enabled = value & 0x0F == 0x0A;
+            }
+            0x2000..0x3000 => {
+                // Lower 8 bits of the ROM bank
+                self.rom_bank = (self.rom_bank & 0x100) | value as u16;
+            }
+            0x3000..0x4000 => {
+                // 9th bit of the ROM bank
+                self.rom_bank = (self.rom_bank & 0xFF) | ((value as u16 & 0x01) << 8);
+            }
+            0x4000..0x6000 => {
+                if self.has_rumble {
+                    self.is_rumbling = value & (1 << RUMBLE_BIT) != 0;
+                    self.ram_bank = value & 0x07;
+                } else {
+                    self.ram_bank = value & 0x0F;
+                }
+            }
+            0x6000..0x8000 => {
+                // No registers here
+            }
+            0xA000..0xC000 => {
+                if let Some(ram_addr) = self.get_ram_address(addr) {
+                    self.ram[ram_addr] = value;
+                    self.is_ram_written = true;
+                }
+            }
+            _ => panic!("Out of bank range"),
+        }
+    }
+
+    fn is_rumbling(&self) -> bool {
+        self.is_rumbling
+    }
+
+    fn take_ram_written(&mut self) -> bool {
+        std::mem::take(&mut self.is_ram_written)
+    }
+
+    fn get_save_data(&self) -> Vec<u8> {
+        self.ram.clone()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data);
+    }
+}
+
+impl Mbc5 {
+    fn get_ram_address(&self, addr: usize) -> Option<usize> {
+        if !self.is_ram_enabled || self.ram.is_empty() {
+            return None;
+        }
+
+        let ram_addr = self.ram_bank as usize * RAM_BANK_SIZE + (addr - 0xA000);
+        Some(ram_addr % self.ram.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::mappers::mapper::ROM_BANK_SIZE;
+
+    // 8 MiB of ROM and 128KiB of RAM, each bank starts with its bank number
+    fn get_mbc5(cartridge_type: u8) -> Mbc5 {
+        let mut rom = vec![0; 0x800000];
+        for bank in 0..0x200 {
+            rom[bank * ROM_BANK_SIZE] = bank as u8;
+            rom[bank * ROM_BANK_SIZE + 1] = (bank >> 8) as u8;
+        }
+        rom[0x0147] = cartridge_type;
+        rom[0x0148] = 0x08;
+        rom[0x0149] = 0x04;
+        let cartridge = Cartridge::from_data("mbc5.gb".to_string(), rom, &Config::default());
+        Mbc5::new(&cartridge.unwrap())
+    }
+
+    #[test]
+    fn rom_bank_has_nine_bits() {
+        let mut mbc5 = get_mbc5(0x1B);
+        mbc5.write(0x2000, 0x00);
+        assert_eq!(mbc5.read(0x4000), 0x00);
+
+        mbc5.write(0x2000, 0x23);
+        mbc5.write(0x3000, 0x01);
+        assert_eq!(mbc5.read(0x4000), 0x23);
+        assert_eq!(mbc5.read(0x4001), 0x01);
+    }
+
+    #[test]
+    fn rumble_takes_a_ram_bank_bit() {
+        let mut mbc5 = get_mbc5(0x1E);
+        mbc5.write(0x0000, 0x0A);
+        mbc5.write(0x4000, 0x09);
+        assert!(mbc5.is_rumbling());
+        mbc5.write(0xA000, 0x42);
+        assert_eq!(mbc5.get_save_data()[RAM_BANK_SIZE], 0x42);
+
+        mbc5.write(0x4000, 0x01);
+        assert!(!mbc5.is_rumbling());
+    }
+
+    #[test]
+    fn cartridges_without_rumble_use_four_ram_bank_bits() {
+        let mut mbc5 = get_mbc5(0x1B);
+        mbc5.write(0x0000, 0x0A);
+        mbc5.write(0x4000, 0x09);
+        assert!(!mbc5.is_rumbling());
+        mbc5.write(0xA000, 0x42);
+        // Bank 9 needs the fourth bit
+        assert_eq!(mbc5.get_save_data()[9 * RAM_BANK_SIZE], 0x42);
+    }
+}
@@ -0,0 +1,443 @@
+use crate::mappers::mapper::{Mapper, read_rom_bank};
+use crate::rom::cartridge::Cartridge;
+use crate::utils::test_bit;
+
+// The accelerometer reads 0x81D0 when flat, and moves roughly 0x70 per g
+const ACCELEROMETER_CENTER: u16 = 0x81D0;
+const ACCELEROMETER_PER_G: f32 = 0x70 as f32;
+// Value of the accelerometer registers after being erased, before the next latch
+const ACCELEROMETER_ERASED: u16 = 0x8000;
+
+// The 93LC56 is 256 bytes, organized as 128 16 bit words
+const EEPROM_WORDS: usize = 128;
+
+// Bits of the EEPROM register (0xAx8x)
+const EEPROM_CS_BIT: u8 = 7;
+const EEPROM_CLK_BIT: u8 = 6;
+const EEPROM_DI_BIT: u8 = 1;
+const EEPROM_DO_BIT: u8 = 0;
+
+// Start bit + 2 bit opcode + 8 bit address
+const EEPROM_COMMAND_BITS: u8 = 11;
+const EEPROM_DATA_BITS: u8 = 16;
+
+#[derive(Debug)]
+pub(crate) struct Mbc7 {
+    rom_data: Vec<u8>,
+    // RAM is enabled by writing 0x0A to 0x0000 - 0x1FFF and 0x40 to 0x4000 - 0x5FFF
+    is_ram_enabled_1: bool,
+    is_ram_enabled_2: bool,
+    rom_bank: u8,
+    // Tilt in g, set by the frontend
+    tilt_x: f32,
+    tilt_y: f32,
+    // Accelerometer values are only updated when the game latches them
+    is_latch_ready: bool,
+    latched_x: u16,
+    latched_y: u16,
+    eeprom: Eeprom,
+}
+
+impl Mapper for Mbc7 {
+    fn new(cartridge: &Cartridge) -> Self {
+        Mbc7 {
+            rom_data: cartridge.get_data().to_vec(),
+            is_ram_enabled_1: false,
+            is_ram_enabled_2: false,
+            rom_bank: 1,
+            tilt_x: 0.0,
+            tilt_y: 0.0,
+            is_latch_ready: false,
+            latched_x: ACCELEROMETER_ERASED,
+            latched_y: ACCELEROMETER_ERASED,
+            eeprom: Eeprom::new(),
+        }
+    }
+
+    fn read(&self, addr: u16) -> u8 {
+        let addr = addr as usize;
+
+        match addr {
+            0x0000..0x4000 => read_rom_bank(&self.rom_data, 0, addr),
+            0x4000..0x8000 => read_rom_bank(&self.rom_data, self.rom_bank as usize, addr - 0x4000),
+            0xA000..0xB000 => {
+                if !self.is_ram_enabled() {
+                    return 0xFF;
+                }
+
+                // Bits 4 - 7 of the address select the register
+                match (addr >> 4) & 0x0F {
+                    0x02 => self.latched_x as u8,
+                    0x03 => (self.latched_x >> 8) as u8,
+                    0x04 => self.latched_y as u8,
+                    0x05 => (self.latched_y >> 8) as u8,
+                    // Unknown register, always reads 0
+                    0x06 => 0x00,
+                    0x08 => self.eeprom.read(),
+                    _ => 0xFF,
+                }
+            }
+            0xB000..0xC000 => 0xFF,
+            _ => panic!("Out of bank range"),
+        }
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        let addr = addr as usize;
+
+        match addr {
+            0x0000..0x2000 => {
+                self.is_ram_enabled_1 = value == 0x0A;
+            }
+            0x2000..0x4000 => {
+                self.rom_bank = value & 0x7F;
+            }
+            0x4000..0x6000 => {
+                self.is_ram_enabled_2 = value == 0x40;
+            }
+            0x6000..0x8000 => {
+                // No registers here
+            }
+            0xA000..0xB000 => {
+                if !self.is_ram_enabled() {
+                    return;
+                }
+
+                match (addr >> 4) & 0x0F {
+                    // Erase the latched values, the next latch needs this first
+                    0x00 if value == 0x55 => {
+                        self.latched_x = ACCELEROMETER_ERASED;
+                        self.latched_y = ACCELEROMETER_ERASED;
+                        self.is_latch_ready = true;
+                    }
+                    0x01 if value == 0xAA && self.is_latch_ready => {
+                        self.latched_x = get_accelerometer_value(self.tilt_x);
+                        self.latched_y = get_accelerometer_value(self.tilt_y);
+                        self.is_latch_ready = false;
+                    }
+                    0x08 => self.eeprom.write(value),
+                    _ => {}
+                }
+            }
+            0xB000..0xC000 => {}
+            _ => panic!("Out of bank range"),
+        }
+    }
+
+    fn set_tilt(&mut self, x: f32, y: f32) {
+        self.tilt_x = x;
+        self.tilt_y = y;
+    }
+
+    fn take_ram_written(&mut self) -> bool {
+        std::mem::take(&mut self.eeprom.is_written)
+    }
+
+    fn get_save_data(&self) -> Vec<u8> {
+        self.eeprom.get_data()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        self.eeprom.load_data(data);
+    }
+}
+
+impl Mbc7 {
+    fn is_ram_enabled(&self) -> bool {
+        self.is_ram_enabled_1 && self.is_ram_enabled_2
+    }
+}
+
+fn get_accelerometer_value(tilt: f32) -> u16 {
+    let offset = (tilt * ACCELEROMETER_PER_G) as i32;
+    (ACCELEROMETER_CENTER as i32 + offset).clamp(0, u16::MAX as i32) as u16
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EepromState {
+    // Waiting for a start bit, then shifting in the opcode and address
+    Command,
+    // Shifting in the 16 bits of data for WRITE and WRAL
+    Data { opcode: u8, address: u8 },
+    // Shifting out 16 bits of data for READ, continues onto the next word while CS is high
+    Read { address: u8, bits_left: u8 },
+}
+
+/**
+ * 93LC56 serial EEPROM, the game bit-bangs the chip select, clock and data lines through the
+ * EEPROM register. Commands and data are shifted in on the rising edge of the clock.
+ */
+#[derive(Debug)]
+struct Eeprom {
+    words: [u16; EEPROM_WORDS],
+    state: EepromState,
+    shift_register: u32,
+    bit_count: u8,
+    is_write_enabled: bool,
+    // Set when a command changes the stored words, used to know when to save
+    is_written: bool,
+    chip_select: bool,
+    clock: bool,
+    data_in: bool,
+    data_out: bool,
+}
+
+impl Eeprom {
+    fn new() -> Self {
+        Eeprom {
+            // Erased EEPROM cells read as 1s
+            words: [0xFFFF; EEPROM_WORDS],
+            state: EepromState::Command,
+            shift_register: 0,
+            bit_count: 0,
+            is_write_enabled: false,
+            is_written: false,
+            chip_select: false,
+            clock: false,
+            data_in: false,
+            data_out: true,
+        }
+    }
+
+    // Words are stored little endian in the save file
+    fn get_data(&self) -> Vec<u8> {
+        self.words
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .collect()
+    }
+
+    fn load_data(&mut self, data: &[u8]) {
+        for (word, bytes) in self.words.iter_mut().zip(data.chunks_exact(2)) {
+            *word = u16::from_le_bytes([bytes[0], bytes[1]]);
+        }
+    }
+
+    fn read(&self) -> u8 {
+        let mut value = 0;
+        value |= (self.chip_select as u8) << EEPROM_CS_BIT;
+        value |= (self.clock as u8) << EEPROM_CLK_BIT;
+        value |= (self.data_in as u8) << EEPROM_DI_BIT;
+        value |= (self.data_out as u8) << EEPROM_DO_BIT;
+        value
+    }
+
+    fn write(&mut self, value: u8) {
+        let chip_select = test_bit(value, EEPROM_CS_BIT);
+        let clock = test_bit(value, EEPROM_CLK_BIT);
+        let data_in = test_bit(value, EEPROM_DI_BIT);
+
+        if !chip_select {
+            // Deselecting the chip aborts whatever command was in progress
+            self.state = EepromState::Command;
+            self.shift_register = 0;
+            self.bit_count = 0;
+        } else if clock && !self.clock {
+            self.on_rising_clock(data_in);
+        }
+
+        self.chip_select = chip_select;
+        self.clock = clock;
+        self.data_in = data_in;
+    }
+
+    fn on_rising_clock(&mut self, data_in: bool) {
+        match self.state {
+            EepromState::Read { address, bits_left } => {
+                let word = self.words[address as usize];
+                self.data_out = word & (1 << (bits_left - 1)) != 0;
+
+                self.state = if bits_left == 1 {
+                    let address = (address + 1) % EEPROM_WORDS as u8;
+                    EepromState::Read {
+                        address,
+                        bits_left: EEPROM_DATA_BITS,
+                    }
+                } else {
+                    EepromState::Read {
+                        address,
+                        bits_left: bits_left - 1,
+                    }
+                };
+            }
+            EepromState::Command => {
+                // Leading zeros before the start bit are ignored
+                if self.bit_count == 0 && !data_in {
+                    return;
+                }
+
+                self.shift_in(data_in);
+                if self.bit_count == EEPROM_COMMAND_BITS {
+                    let opcode = ((self.shift_register >> 8) & 0x03) as u8;
+                    let address = self.shift_register as u8;
+                    self.shift_register = 0;
+                    self.bit_count = 0;
+                    self.handle_command(opcode, address);
+                }
+            }
+            EepromState::Data { opcode, address } => {
+                self.shift_in(data_in);
+                if self.bit_count == EEPROM_DATA_BITS {
+                    let data = self.shift_register as u16;
+                    self.shift_register = 0;
+                    self.bit_count = 0;
+                    self.state = EepromState::Command;
+                    self.handle_data(opcode, address, data);
+                }
+            }
+        }
+    }
+
+    fn shift_in(&mut self, data_in: bool) {
+        self.shift_register = (self.shift_register << 1) | data_in as u32;
+        self.bit_count += 1;
+    }
+
+    fn handle_command(&mut self, opcode: u8, address: u8) {
+        // In 16 bit organization only the lower 7 address bits are used
+        let word = address & 0x7F;
+
+        match opcode {
+            // READ, a dummy 0 bit is output before the data
+            0b10 => {
+                self.data_out = false;
+                self.state = EepromState::Read {
+                    address: word,
+                    bits_left: EEPROM_DATA_BITS,
+                };
+            }
+            // WRITE
+            0b01 => {
+                self.state = EepromState::Data {
+                    opcode,
+                    address: word,
+                };
+            }
+            // ERASE
+            0b11 => {
+                if self.is_write_enabled {
+                    self.words[word as usize] = 0xFFFF;
+                    self.is_written = true;
+                }
+                self.data_out = true;
+            }
+            // The top 2 address bits select the extended commands
+            _ => match address >> 6 {
+                // EWDS
+                0b00 => self.is_write_enabled = false,
+                // WRAL
+                0b01 => {
+                    self.state = EepromState::Data { opcode, address };
+                }
+                // ERAL
+                0b10 => {
+                    if self.is_write_enabled {
+                        self.words = [0xFFFF; EEPROM_WORDS];
+                        self.is_written = true;
+                    }
+                    self.data_out = true;
+                }
+                // EWEN
+                0b11 => self.is_write_enabled = true,
+                _ => unreachable!(),
+            },
+        }
+    }
+
+    fn handle_data(&mut self, opcode: u8, address: u8, data: u16) {
+        if self.is_write_enabled {
+            if opcode == 0b01 {
+                self.words[address as usize] = data;
+            } else {
+                self.words = [data; EEPROM_WORDS];
+            }
+            self.is_written = true;
+        }
+        // Writes complete instantly, so the chip is always ready
+        self.data_out = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn get_mbc7() -> Mbc7 {
+        let mut rom = vec![0; 0x80000];
+        rom[0x0147] = 0x22;
+        rom[0x0148] = 0x04;
+        let cartridge = Cartridge::from_data("mbc7.gb".to_string(), rom, &Config::default());
+        let mut mbc7 = Mbc7::new(&cartridge.unwrap());
+        mbc7.write(0x0000, 0x0A);
+        mbc7.write(0x4000, 0x40);
+        mbc7
+    }
+
+    // Clock bits into the EEPROM with chip select held high, most significant bit first
+    fn send_bits(eeprom: &mut Eeprom, bits: u32, count: u8) {
+        for i in (0..count).rev() {
+            let data_in = (((bits >> i) & 0x01) as u8) << EEPROM_DI_BIT;
+            eeprom.write((1 << EEPROM_CS_BIT) | data_in);
+            eeprom.write((1 << EEPROM_CS_BIT) | (1 << EEPROM_CLK_BIT) | data_in);
+        }
+    }
+
+    fn read_word(eeprom: &mut Eeprom) -> u16 {
+        let mut word = 0;
+        for _ in 0..EEPROM_DATA_BITS {
+            send_bits(eeprom, 0, 1);
+            word = (word << 1) | (eeprom.read() & 0x01) as u16;
+        }
+        word
+    }
+
+    #[test]
+    fn accelerometer_latches_after_erase() {
+        let mut mbc7 = get_mbc7();
+        mbc7.set_tilt(1.0, -1.0);
+
+        // Latching without erasing first does nothing
+        mbc7.write(0xA010, 0xAA);
+        assert_eq!(mbc7.read(0xA020), 0x00);
+        assert_eq!(mbc7.read(0xA030), 0x80);
+
+        mbc7.write(0xA000, 0x55);
+        mbc7.write(0xA010, 0xAA);
+        let x = u16::from_le_bytes([mbc7.read(0xA020), mbc7.read(0xA030)]);
+        let y = u16::from_le_bytes([mbc7.read(0xA040), mbc7.read(0xA050)]);
+        assert_eq!(x, ACCELEROMETER_CENTER + 0x70);
+        assert_eq!(y, ACCELEROMETER_CENTER - 0x70);
+    }
+
+    #[test]
+    fn eeprom_needs_write_enable() {
+        let mut eeprom = Eeprom::new();
+        // WRITE 0x1234 to word 5
+        send_bits(&mut eeprom, 0b101_0000_0101, EEPROM_COMMAND_BITS);
+        send_bits(&mut eeprom, 0x1234, EEPROM_DATA_BITS);
+        assert!(!eeprom.is_written);
+
+        // EWEN, then WRITE again
+        send_bits(&mut eeprom, 0b100_1100_0000, EEPROM_COMMAND_BITS);
+        send_bits(&mut eeprom, 0b101_0000_0101, EEPROM_COMMAND_BITS);
+        send_bits(&mut eeprom, 0x1234, EEPROM_DATA_BITS);
+        assert!(eeprom.is_written);
+        assert_eq!(eeprom.get_data()[10..12], [0x34, 0x12]);
+    }
+
+    #[test]
+    fn eeprom_reads_back_words() {
+        let mut eeprom = Eeprom::new();
+        let mut data = vec![0; EEPROM_WORDS * 2];
+        data[10..12].copy_from_slice(&[0xCD, 0xAB]);
+        eeprom.load_data(&data);
+
+        // READ word 5, a dummy 0 comes before the data
+        send_bits(&mut eeprom, 0b110_0000_0101, EEPROM_COMMAND_BITS);
+        assert_eq!(eeprom.read() & 0x01, 0);
+        assert_eq!(read_word(&mut eeprom), 0xABCD);
+        // Reads continue onto the next word
+        assert_eq!(read_word(&mut eeprom), 0x0000);
+    }
+}
@@ -0,0 +1,287 @@
+use crate::mappers::mapper::{
+    Mapper, RAM_BANK_SIZE, ROM_BANK_SIZE, get_external_ram_size, load_ram, read_rom_bank,
+};
+use crate::rom::cartridge::Cartridge;
+use crate::rom::rom_header::{
+    CARTRIDGE_TYPE_ADDR, HEADER_CHECKSUM_ADDR, LOGO_START, NINTENDO_LOGO,
+};
+use crate::rom::validation::compute_header_checksum;
+use crate::utils::test_bit;
+
+// Bit 6 of 0x0000 - 0x1FFF locks the outer bank configuration
+const LOCK_BIT: u8 = 6;
+// Bit 6 of 0x4000 - 0x5FFF stops the game from changing the banking mode
+const MODE_WRITE_PROTECT_BIT: u8 = 6;
+// Bit 6 of 0x6000 - 0x7FFF swaps the ROM bank mid bits with the RAM bank low bits
+const MULTIPLEX_BIT: u8 = 6;
+// The menu is in the last 32KiB, so its header is there too
+const MENU_SIZE: usize = 0x8000;
+
+/**
+ * MMM01 multi-game cartridges boot into a menu stored in the last 32KiB of ROM. The menu selects a
+ * game by writing the outer bank bits and then locking them, after which the game sees a MBC1.
+ */
+#[derive(Debug)]
+pub(crate) struct Mmm01 {
+    rom_data: Vec<u8>,
+    ram: Vec<u8>,
+    // Set when the game writes to RAM, used to know when to save
+    is_ram_written: bool,
+    is_locked: bool,
+    is_ram_enabled: bool,
+    // ROM bank is split into low (5 bits, MBC1 register), mid (2 bits) and high (2 bits)
+    rom_bank_low: u8,
+    rom_bank_mid: u8,
+    rom_bank_high: u8,
+    // Bits set here keep bits 1-4 of the ROM bank low register fixed once locked
+    rom_bank_mask: u8,
+    // RAM bank is split into low (2 bits, MBC1 register) and high (2 bits)
+    ram_bank_low: u8,
+    ram_bank_high: u8,
+    // Bits set here keep the RAM bank low bits fixed once locked
+    ram_bank_mask: u8,
+    is_advanced_banking_mode: bool,
+    is_mode_write_protected: bool,
+    is_multiplexed: bool,
+}
+
+impl Mapper for Mmm01 {
+    fn new(cartridge: &Cartridge) -> Self {
+        Mmm01 {
+            rom_data: cartridge.get_data().to_vec(),
+            ram: vec![0; get_external_ram_size(cartridge)],
+            is_ram_written: false,
+            is_locked: false,
+            is_ram_enabled: false,
+            rom_bank_low: 0,
+            rom_bank_mid: 0,
+            rom_bank_high: 0,
+            rom_bank_mask: 0,
+            ram_bank_low: 0,
+            ram_bank_high: 0,
+            ram_bank_mask: 0,
+            is_advanced_banking_mode: false,
+            is_mode_write_protected: false,
+            is_multiplexed: false,
+        }
+    }
+
+    fn read(&self, addr: u16) -> u8 {
+        let addr = addr as usize;
+
+        if !self.is_locked && addr < 0x8000 {
+            // The menu in the last 32KiB is mapped until the configuration is locked
+            let bank_count = (self.rom_data.len() / ROM_BANK_SIZE).max(2);
+            let bank = bank_count - 2 + addr / ROM_BANK_SIZE;
+            return read_rom_bank(&self.rom_data, bank, addr % ROM_BANK_SIZE);
+        }
+
+        match addr {
+            0x0000..0x4000 => {
+                // The first bank of the selected game, the low bits not held by the mask are 0
+                let fixed_low = self.rom_bank_low & self.get_rom_bank_low_mask();
+                let bank = self.get_outer_rom_bank() | fixed_low as usize;
+                read_rom_bank(&self.rom_data, bank, addr)
+            }
+            0x4000..0x8000 => {
+                let bank = self.get_outer_rom_bank() | self.rom_bank_low as usize;
+                read_rom_bank(&self.rom_data, bank, addr - 0x4000)
+            }
+            0xA000..0xC000 => match self.get_ram_address(addr) {
+                Some(ram_addr) => self.ram[ram_addr],
+                None => 0xFF,
+            },
+            _ => panic!("Out of bank range"),
+        }
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        let addr = addr as usize;
+
+        match addr {
+            0x0000..0x2000 => {
+                self.is_ram_enabled = value & 0x0F == 0x0A;
+                if !self.is_locked {
+                    self.ram_bank_mask = (value >> 4) & 0x03;
+                    self.is_locked = test_bit(value, LOCK_BIT);
+                }
+            }
+            0x2000..0x4000 => {
+                // Masked bits can't be changed once locked
+                let mask = if self.is_locked {
+                    self.get_rom_bank_low_mask()
+                } else {
+                    0
+                };
+                let mut bank = (self.rom_bank_low & mask) | (value & 0x1F & !mask);
+                // Same as MBC1, selecting bank 0 of the game selects bank 1
+                if bank & !mask == 0 {
+                    bank |= 0x01;
+                }
+                self.rom_bank_low = bank;
+
+                if !self.is_locked {
+                    self.rom_bank_mid = (value >> 5) & 0x03;
+                }
+            }
+            0x4000..0x6000 => {
+                let mask = if self.is_locked {
+                    self.ram_bank_mask
+                } else {
+                    0
+                };
+                self.ram_bank_low = (self.ram_bank_low & mask) | (value & 0x03 & !mask);
+
+                if !self.is_locked {
+                    self.ram_bank_high = (value >> 2) & 0x03;
+                    self.rom_bank_high = (value >> 4) & 0x03;
+                    self.is_mode_write_protected = test_bit(value, MODE_WRITE_PROTECT_BIT);
+                }
+            }
+            0x6000..0x8000 => {
+                if !self.is_locked || !self.is_mode_write_protected {
+                    self.is_advanced_banking_mode = value & 0x01 == 1;
+                }
+
+                if !self.is_locked {
+                    self.rom_bank_mask = (value >> 2) & 0x0F;
+                    self.is_multiplexed = test_bit(value, MULTIPLEX_BIT);
+                }
+            }
+            0xA000..0xC000 => {
+                if let Some(ram_addr) = self.get_ram_address(addr) {
+                    self.ram[ram_addr] = value;
+                    self.is_ram_written = true;
+                }
+            }
+            _ => panic!("Out of bank range"),
+        }
+    }
+
+    fn take_ram_written(&mut self) -> bool {
+        std::mem::take(&mut self.is_ram_written)
+    }
+
+    fn get_save_data(&self) -> Vec<u8> {
+        self.ram.clone()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data);
+    }
+}
+
+impl Mmm01 {
+    fn get_rom_bank_low_mask(&self) -> u8 {
+        // The mask covers bits 1-4 of the 5 bit register
+        self.rom_bank_mask << 1
+    }
+
+    fn get_outer_rom_bank(&self) -> usize {
+        let mid = if self.is_multiplexed && self.is_advanced_banking_mode {
+            self.ram_bank_low
+        } else {
+            self.rom_bank_mid
+        };
+        ((self.rom_bank_high as usize) << 7) | ((mid as usize) << 5)
+    }
+
+    fn get_ram_address(&self, addr: usize) -> Option<usize> {
+        if !self.is_ram_enabled || self.ram.is_empty() {
+            return None;
+        }
+
+        let low = if self.is_multiplexed && self.is_advanced_banking_mode {
+            self.rom_bank_mid
+        } else if self.is_advanced_banking_mode {
+            self.ram_bank_low
+        } else {
+            0
+        };
+        let bank = ((self.ram_bank_high as usize) << 2) | low as usize;
+        let ram_addr = bank * RAM_BANK_SIZE + (addr - 0xA000);
+        Some(ram_addr % self.ram.len())
+    }
+}
+
+/**
+ * The header at the start of the ROM belongs to the first game, the MMM01 header is the menu's.
+ * A single byte could be anything in the last 32KiB of a normal game, so the menu header also
+ * has to pass the boot ROM checks.
+ */
+pub(crate) fn is_mmm01(rom_data: &[u8]) -> bool {
+    if rom_data.len() <= MENU_SIZE {
+        return false;
+    }
+
+    let menu = &rom_data[rom_data.len() - MENU_SIZE..];
+    matches!(menu[CARTRIDGE_TYPE_ADDR], 0x0B..=0x0D)
+        && menu[LOGO_START..LOGO_START + NINTENDO_LOGO.len()] == NINTENDO_LOGO
+        && menu[HEADER_CHECKSUM_ADDR] == compute_header_checksum(menu)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    // 1 MiB of ROM, each bank starts with its bank number and the menu header is MMM01
+    fn get_rom() -> Vec<u8> {
+        let mut rom = vec![0; 0x100000];
+        for bank in 0..rom.len() / ROM_BANK_SIZE {
+            rom[bank * ROM_BANK_SIZE] = bank as u8;
+        }
+        let menu_start = rom.len() - MENU_SIZE;
+        let menu = &mut rom[menu_start..];
+        menu[LOGO_START..LOGO_START + NINTENDO_LOGO.len()].copy_from_slice(&NINTENDO_LOGO);
+        menu[CARTRIDGE_TYPE_ADDR] = 0x0B;
+        menu[HEADER_CHECKSUM_ADDR] = compute_header_checksum(menu);
+        rom
+    }
+
+    #[test]
+    fn detects_menu_header() {
+        let rom = get_rom();
+        assert!(is_mmm01(&rom));
+        assert!(!is_mmm01(&rom[..MENU_SIZE]));
+
+        let menu_start = rom.len() - MENU_SIZE;
+        let mut wrong_type = rom.clone();
+        wrong_type[menu_start + CARTRIDGE_TYPE_ADDR] = 0x01;
+        assert!(!is_mmm01(&wrong_type));
+
+        // The menu header has to pass the boot ROM checks too
+        let mut bad_logo = rom.clone();
+        bad_logo[menu_start + LOGO_START] = 0x00;
+        assert!(!is_mmm01(&bad_logo));
+        let mut bad_checksum = rom;
+        bad_checksum[menu_start + HEADER_CHECKSUM_ADDR] ^= 0xFF;
+        assert!(!is_mmm01(&bad_checksum));
+    }
+
+    #[test]
+    fn locked_game_stays_in_its_banks() {
+        let cartridge = Cartridge::from_data("mmm01.gb".to_string(), get_rom(), &Config::default());
+        let mut mmm01 = Mmm01::new(&cartridge.unwrap());
+        // The menu is mapped at boot
+        assert_eq!(mmm01.read(0x0000), 62);
+        assert_eq!(mmm01.read(0x4000), 63);
+
+        // The menu selects an 8 bank game at bank 0x20 and locks it
+        mmm01.write(0x2000, 0x20);
+        mmm01.write(0x6000, 0x30);
+        mmm01.write(0x0000, 0x40);
+        assert_eq!(mmm01.read(0x0000), 0x20);
+        assert_eq!(mmm01.read(0x4000), 0x21);
+
+        mmm01.write(0x2000, 0x03);
+        assert_eq!(mmm01.read(0x4000), 0x23);
+        mmm01.write(0x2000, 0x1F);
+        assert_eq!(mmm01.read(0x4000), 0x27);
+
+        // The outer bank can't be changed once locked
+        mmm01.write(0x4000, 0x30);
+        mmm01.write(0x0000, 0x00);
+        assert_eq!(mmm01.read(0x0000), 0x20);
+    }
+}
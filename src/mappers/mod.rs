@@ -1,3 +1,18 @@
+pub mod camera;
+pub mod huc1;
+pub mod huc3;
+pub mod infrared;
+pub mod m161;
 pub mod mapper;
 pub mod mbc1;
+pub mod mbc2;
+pub mod mbc3;
+pub mod mbc5;
+pub mod mbc7;
+pub mod mmm01;
 pub mod no_mbc;
+pub mod rtc;
+pub mod sachen;
+pub mod speaker;
+pub mod tama5;
+pub mod wisdom_tree;
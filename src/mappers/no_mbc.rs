@@ -1,15 +1,21 @@
-use crate::mappers::mapper::Mapper;
+use crate::mappers::mapper::{Mapper, get_external_ram_size, load_ram};
 use crate::rom::cartridge::Cartridge;
 
 #[derive(Debug)]
 pub(crate) struct NoMbc {
     rom_data: Vec<u8>,
+    // Up to 8KiB of RAM can be wired up without a MBC
+    ram: Vec<u8>,
+    // Set when the game writes to RAM, used to know when to save
+    is_ram_written: bool,
 }
 
 impl Mapper for NoMbc {
     fn new(cartridge: &Cartridge) -> Self {
         NoMbc {
             rom_data: cartridge.get_data().to_vec(),
+            ram: vec![0; get_external_ram_size(cartridge).min(0x2000)],
+            is_ram_written: false,
         }
     }
 
@@ -17,12 +23,37 @@ impl Mapper for NoMbc {
         let addr = addr as usize;
 
         match addr {
-            0x0000..0x8000 => self.rom_data[addr],
+            0x0000..0x8000 => self.rom_data.get(addr).copied().unwrap_or(0xFF),
+            0xA000..0xC000 => self.ram.get(addr - 0xA000).copied().unwrap_or(0xFF),
             _ => panic!("Out of bank range"),
         }
     }
 
     fn write(&mut self, addr: u16, value: u8) {
-        // rom is read only
+        let addr = addr as usize;
+
+        match addr {
+            // rom is read only
+            0x0000..0x8000 => {}
+            0xA000..0xC000 => {
+                if let Some(byte) = self.ram.get_mut(addr - 0xA000) {
+                    *byte = value;
+                    self.is_ram_written = true;
+                }
+            }
+            _ => panic!("Out of bank range"),
+        }
+    }
+
+    fn take_ram_written(&mut self) -> bool {
+        std::mem::take(&mut self.is_ram_written)
+    }
+
+    fn get_save_data(&self) -> Vec<u8> {
+        self.ram.clone()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        load_ram(&mut self.ram, data);
     }
 }
@@ -0,0 +1,527 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::Deserialize;
+
+// The RTC has its own 32.768 KHz crystal, but the emulated clock is advanced from CPU M-cycles so
+// headless runs are deterministic
+const CYCLES_PER_SECOND: usize = 1_048_576;
+
+const SECONDS_REGISTER: u8 = 0x08;
+const MINUTES_REGISTER: u8 = 0x09;
+const HOURS_REGISTER: u8 = 0x0A;
+const DAY_LOW_REGISTER: u8 = 0x0B;
+const DAY_HIGH_REGISTER: u8 = 0x0C;
+
+// Bits in the day high register
+const DAY_HIGH_BIT: u8 = 0;
+const HALT_BIT: u8 = 6;
+const DAY_CARRY_BIT: u8 = 7;
+
+// Save footer used by VBA-M, BGB and SameBoy, the clock and latched registers as 32 bit words
+// followed by a UNIX timestamp. Older saves store the timestamp in 32 bits.
+const RTC_FOOTER_SIZE: usize = 48;
+const LEGACY_RTC_FOOTER_SIZE: usize = 44;
+const REGISTERS_SIZE: usize = 5 * 4;
+// RAM sizes are all multiples of 512 bytes, so whatever is left over is a footer
+const RAM_SIZE_ALIGNMENT: usize = 0x200;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RtcMode {
+    // Advance the clock from emulated time
+    Emulated,
+    // Keep the clock in sync with the host's clock
+    Host,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct RtcRegisters {
+    pub seconds: u8,
+    pub minutes: u8,
+    pub hours: u8,
+    pub day_low: u8,
+    pub day_high: u8,
+}
+
+impl RtcRegisters {
+    fn to_bytes(self) -> Vec<u8> {
+        [
+            self.seconds,
+            self.minutes,
+            self.hours,
+            self.day_low,
+            self.day_high,
+        ]
+        .iter()
+        .flat_map(|register| (*register as u32).to_le_bytes())
+        .collect()
+    }
+
+    fn from_bytes(data: &[u8]) -> Self {
+        // Only the low byte of each word is used
+        let register = |index: usize| data[index * 4];
+        RtcRegisters {
+            seconds: register(0),
+            minutes: register(1),
+            hours: register(2),
+            day_low: register(3),
+            day_high: register(4),
+        }
+    }
+}
+
+/**
+ * Clock state stored after the RAM in .sav files, the timestamp lets the clock catch up on the
+ * time that passed while the emulator was closed
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct RtcFooter {
+    pub registers: RtcRegisters,
+    pub latched: RtcRegisters,
+    // Seconds since the UNIX epoch when the registers were last up to date
+    pub timestamp: u64,
+}
+
+impl RtcFooter {
+    /**
+     * Split a save into the RAM and the RTC footer, saves without a footer are all RAM
+     */
+    pub fn split(data: &[u8], ram_size: usize) -> (&[u8], Option<Self>) {
+        let (ram, footer) =
+            split_footer(data, ram_size, &[RTC_FOOTER_SIZE, LEGACY_RTC_FOOTER_SIZE]);
+        let Some(footer) = footer else {
+            return (data, None);
+        };
+
+        let timestamp = &footer[REGISTERS_SIZE * 2..];
+        let timestamp = if footer.len() == RTC_FOOTER_SIZE {
+            u64::from_le_bytes(timestamp.try_into().unwrap())
+        } else {
+            u32::from_le_bytes(timestamp.try_into().unwrap()) as u64
+        };
+
+        let footer = RtcFooter {
+            registers: RtcRegisters::from_bytes(&footer[..REGISTERS_SIZE]),
+            latched: RtcRegisters::from_bytes(&footer[REGISTERS_SIZE..REGISTERS_SIZE * 2]),
+            timestamp,
+        };
+        (ram, Some(footer))
+    }
+
+    /**
+     * Always written in the 48 byte format
+     */
+    pub fn to_bytes(self) -> Vec<u8> {
+        let mut data = self.registers.to_bytes();
+        data.extend(self.latched.to_bytes());
+        data.extend(self.timestamp.to_le_bytes());
+        data
+    }
+
+    /**
+     * Seconds of real time that passed since the footer was written
+     */
+    pub fn elapsed(&self) -> u64 {
+        get_elapsed(self.timestamp)
+    }
+}
+
+/**
+ * Split a save into the RAM and a footer of one of the given sizes. The footer is found even when
+ * the save has no RAM, or more or less RAM than the header says.
+ */
+pub(crate) fn split_footer<'a>(
+    data: &'a [u8],
+    ram_size: usize,
+    footer_sizes: &[usize],
+) -> (&'a [u8], Option<&'a [u8]>) {
+    let footer_size = [
+        data.len().saturating_sub(ram_size),
+        data.len() % RAM_SIZE_ALIGNMENT,
+    ]
+    .into_iter()
+    .find(|size| footer_sizes.contains(size));
+
+    match footer_size {
+        Some(footer_size) => {
+            let (ram, footer) = data.split_at(data.len() - footer_size);
+            (ram, Some(footer))
+        }
+        None => (data, None),
+    }
+}
+
+/**
+ * Seconds of real time that passed since a UNIX timestamp
+ */
+pub(crate) fn get_elapsed(timestamp: u64) -> u64 {
+    get_unix_time(SystemTime::now()).saturating_sub(timestamp)
+}
+
+fn get_unix_time(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/**
+ * Time source shared by every cartridge with a clock, counts whole seconds from either emulated
+ * time or the host's clock
+ */
+#[derive(Debug)]
+pub(crate) struct RtcClock {
+    mode: RtcMode,
+    // M-cycles since the last second ticked over
+    cycles: usize,
+    // Host time the clock was last synced at, only used in host mode
+    last_sync: SystemTime,
+}
+
+impl RtcClock {
+    pub fn new(mode: RtcMode) -> Self {
+        RtcClock {
+            mode,
+            cycles: 0,
+            last_sync: SystemTime::now(),
+        }
+    }
+
+    /**
+     * Switch modes, returns the seconds that passed in the old mode since the last sync
+     */
+    pub fn set_mode(&mut self, mode: RtcMode) -> u64 {
+        let seconds = self.sync();
+        self.mode = mode;
+        self.last_sync = SystemTime::now();
+        seconds
+    }
+
+    /**
+     * Returns the number of seconds that passed in emulated time
+     */
+    pub fn step(&mut self, cycles: usize) -> u64 {
+        if self.mode != RtcMode::Emulated {
+            return 0;
+        }
+
+        self.cycles += cycles;
+        let seconds = self.cycles / CYCLES_PER_SECOND;
+        self.cycles %= CYCLES_PER_SECOND;
+        seconds as u64
+    }
+
+    /**
+     * Returns the number of seconds that passed on the host since the last sync
+     */
+    pub fn sync(&mut self) -> u64 {
+        if self.mode != RtcMode::Host {
+            return 0;
+        }
+
+        let now = SystemTime::now();
+        let elapsed = now.duration_since(self.last_sync).unwrap_or_default();
+        let seconds = elapsed.as_secs();
+        // Keep the fractional part of the second for the next sync
+        self.last_sync += Duration::from_secs(seconds);
+        seconds
+    }
+
+    pub fn reset_sub_second(&mut self) {
+        self.cycles = 0;
+    }
+
+    /**
+     * UNIX time the clock was last up to date at, host mode only catches up when synced
+     */
+    pub fn get_timestamp(&self) -> u64 {
+        match self.mode {
+            RtcMode::Emulated => get_unix_time(SystemTime::now()),
+            RtcMode::Host => get_unix_time(self.last_sync),
+        }
+    }
+}
+
+/**
+ * MBC3 clock, counts seconds, minutes, hours and days in separate registers
+ */
+#[derive(Debug)]
+pub(crate) struct Rtc {
+    clock: RtcClock,
+    // The live clock
+    registers: RtcRegisters,
+    // A snapshot of the clock, taken when the game latches it
+    latched: RtcRegisters,
+}
+
+impl Rtc {
+    pub fn new(mode: RtcMode) -> Self {
+        Rtc {
+            clock: RtcClock::new(mode),
+            registers: RtcRegisters::default(),
+            latched: RtcRegisters::default(),
+        }
+    }
+
+    pub fn set_mode(&mut self, mode: RtcMode) {
+        let seconds = self.clock.set_mode(mode);
+        self.advance(seconds);
+    }
+
+    pub fn step(&mut self, cycles: usize) {
+        let seconds = self.clock.step(cycles);
+        self.advance(seconds);
+    }
+
+    pub fn get_footer(&self) -> RtcFooter {
+        RtcFooter {
+            registers: self.registers,
+            latched: self.latched,
+            timestamp: self.clock.get_timestamp(),
+        }
+    }
+
+    /**
+     * Restore the clock from a save, then catch up on the time that passed since it was written
+     */
+    pub fn load_footer(&mut self, footer: RtcFooter) {
+        self.registers = footer.registers;
+        self.latched = footer.latched;
+        self.advance(footer.elapsed());
+    }
+
+    pub fn latch(&mut self) {
+        self.sync();
+        self.latched = self.registers;
+    }
+
+    pub fn read(&self, register: u8) -> u8 {
+        // Unused bits read back as 1s
+        match register {
+            SECONDS_REGISTER => self.latched.seconds | 0xC0,
+            MINUTES_REGISTER => self.latched.minutes | 0xC0,
+            HOURS_REGISTER => self.latched.hours | 0xE0,
+            DAY_LOW_REGISTER => self.latched.day_low,
+            DAY_HIGH_REGISTER => self.latched.day_high | 0x3E,
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write(&mut self, register: u8, value: u8) {
+        self.sync();
+
+        match register {
+            SECONDS_REGISTER => {
+                // Writing to the seconds register resets the sub-second counter
+                self.clock.reset_sub_second();
+                self.registers.seconds = value & 0x3F;
+            }
+            MINUTES_REGISTER => self.registers.minutes = value & 0x3F,
+            HOURS_REGISTER => self.registers.hours = value & 0x1F,
+            DAY_LOW_REGISTER => self.registers.day_low = value,
+            DAY_HIGH_REGISTER => self.registers.day_high = value & 0xC1,
+            _ => {}
+        }
+    }
+
+    /**
+     * Advance the clock by a number of seconds, this is how time passes while the emulator is closed
+     */
+    pub fn advance(&mut self, seconds: u64) {
+        if self.is_halted() {
+            return;
+        }
+
+        // Out of range values have to be ticked through, they wrap around without carrying
+        let mut seconds = seconds;
+        while seconds > 0 && !self.is_in_range() {
+            self.tick();
+            seconds -= 1;
+        }
+        if seconds == 0 {
+            return;
+        }
+
+        // A save can be months old, so skip ahead instead of ticking every second
+        let registers = &mut self.registers;
+        let time = registers.seconds as u64
+            + registers.minutes as u64 * 60
+            + registers.hours as u64 * 60 * 60
+            + seconds;
+        registers.seconds = (time % 60) as u8;
+        registers.minutes = (time / 60 % 60) as u8;
+        registers.hours = (time / (60 * 60) % 24) as u8;
+
+        let day_high = (registers.day_high >> DAY_HIGH_BIT) & 0x01;
+        let day = (((day_high as u64) << 8) | registers.day_low as u64) + time / SECONDS_PER_DAY;
+        self.set_day(day);
+    }
+
+    fn is_in_range(&self) -> bool {
+        self.registers.seconds < 60 && self.registers.minutes < 60 && self.registers.hours < 24
+    }
+
+    fn is_halted(&self) -> bool {
+        self.registers.day_high & (1 << HALT_BIT) != 0
+    }
+
+    fn sync(&mut self) {
+        let seconds = self.clock.sync();
+        self.advance(seconds);
+    }
+
+    fn tick(&mut self) {
+        // Registers wrap at their bit width, so out of range values written by the game
+        // count up to the bit width before wrapping without carrying
+        self.registers.seconds = (self.registers.seconds + 1) & 0x3F;
+        if self.registers.seconds != 60 {
+            return;
+        }
+        self.registers.seconds = 0;
+
+        self.registers.minutes = (self.registers.minutes + 1) & 0x3F;
+        if self.registers.minutes != 60 {
+            return;
+        }
+        self.registers.minutes = 0;
+
+        self.registers.hours = (self.registers.hours + 1) & 0x1F;
+        if self.registers.hours != 24 {
+            return;
+        }
+        self.registers.hours = 0;
+
+        let day_high = (self.registers.day_high >> DAY_HIGH_BIT) & 0x01;
+        let day = (((day_high as u64) << 8) | self.registers.day_low as u64) + 1;
+        self.set_day(day);
+    }
+
+    fn set_day(&mut self, day: u64) {
+        self.registers.day_low = day as u8;
+        // The day counter is 9 bits, overflowing sets the carry bit until the game clears it
+        self.registers.day_high &= !(1 << DAY_HIGH_BIT);
+        self.registers.day_high |= (((day >> 8) & 0x01) as u8) << DAY_HIGH_BIT;
+        if day > 0x1FF {
+            self.registers.day_high |= 1 << DAY_CARRY_BIT;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REGISTERS: RtcRegisters = RtcRegisters {
+        seconds: 1,
+        minutes: 2,
+        hours: 3,
+        day_low: 4,
+        day_high: 5,
+    };
+
+    fn legacy_footer(timestamp: u32) -> Vec<u8> {
+        let mut footer = REGISTERS.to_bytes();
+        footer.extend(REGISTERS.to_bytes());
+        footer.extend(timestamp.to_le_bytes());
+        footer
+    }
+
+    #[test]
+    fn splits_footer_after_ram() {
+        let footer = RtcFooter {
+            registers: REGISTERS,
+            latched: RtcRegisters::default(),
+            timestamp: 0x1_0000_0000,
+        };
+        let mut data = vec![0xAA; 0x2000];
+        data.extend(footer.to_bytes());
+        assert_eq!(footer.to_bytes().len(), RTC_FOOTER_SIZE);
+
+        let (ram, split) = RtcFooter::split(&data, 0x2000);
+        assert_eq!(ram, &[0xAA; 0x2000]);
+        assert_eq!(split, Some(footer));
+    }
+
+    #[test]
+    fn splits_legacy_footer() {
+        let mut data = vec![0xAA; 0x2000];
+        data.extend(legacy_footer(1234));
+        assert_eq!(legacy_footer(1234).len(), LEGACY_RTC_FOOTER_SIZE);
+
+        let (ram, footer) = RtcFooter::split(&data, 0x2000);
+        assert_eq!(ram.len(), 0x2000);
+        let footer = footer.unwrap();
+        assert_eq!(footer.registers, REGISTERS);
+        assert_eq!(footer.latched, REGISTERS);
+        assert_eq!(footer.timestamp, 1234);
+    }
+
+    #[test]
+    fn splits_footer_without_ram() {
+        let data = legacy_footer(1234);
+        let (ram, footer) = RtcFooter::split(&data, 0);
+        assert!(ram.is_empty());
+        assert!(footer.is_some());
+
+        // Saves with only a footer are found even when the header claims RAM
+        let data = empty_footer();
+        let (ram, footer) = RtcFooter::split(&data, 0x8000);
+        assert!(ram.is_empty());
+        assert!(footer.is_some());
+    }
+
+    #[test]
+    fn splits_footer_when_ram_size_differs() {
+        let mut data = vec![0xAA; 0x8000];
+        data.extend(empty_footer());
+        let (ram, footer) = RtcFooter::split(&data, 0x2000);
+        assert_eq!(ram.len(), 0x8000);
+        assert!(footer.is_some());
+    }
+
+    #[test]
+    fn saves_without_footer_are_all_ram() {
+        let data = vec![0xAA; 0x2000];
+        let (ram, footer) = RtcFooter::split(&data, 0x2000);
+        assert_eq!(ram.len(), 0x2000);
+        assert!(footer.is_none());
+    }
+
+    #[test]
+    fn advances_across_days() {
+        let mut rtc = Rtc::new(RtcMode::Emulated);
+        rtc.advance(SECONDS_PER_DAY * 2 + 61);
+        rtc.latch();
+        assert_eq!(rtc.read(SECONDS_REGISTER) & 0x3F, 1);
+        assert_eq!(rtc.read(MINUTES_REGISTER) & 0x3F, 1);
+        assert_eq!(rtc.read(DAY_LOW_REGISTER), 2);
+    }
+
+    #[test]
+    fn sets_carry_when_days_overflow() {
+        let mut rtc = Rtc::new(RtcMode::Emulated);
+        rtc.advance(SECONDS_PER_DAY * 0x200);
+        rtc.latch();
+        assert_eq!(rtc.read(DAY_LOW_REGISTER), 0);
+        assert_ne!(rtc.read(DAY_HIGH_REGISTER) & (1 << DAY_CARRY_BIT), 0);
+    }
+
+    #[test]
+    fn halted_clock_does_not_advance() {
+        let mut rtc = Rtc::new(RtcMode::Emulated);
+        rtc.write(DAY_HIGH_REGISTER, 1 << HALT_BIT);
+        rtc.advance(100);
+        rtc.latch();
+        assert_eq!(rtc.read(SECONDS_REGISTER) & 0x3F, 0);
+    }
+
+    fn empty_footer() -> Vec<u8> {
+        RtcFooter {
+            registers: RtcRegisters::default(),
+            latched: RtcRegisters::default(),
+            timestamp: 0,
+        }
+        .to_bytes()
+    }
+}
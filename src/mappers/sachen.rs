@@ -0,0 +1,216 @@
+use std::cell::Cell;
+
+use crate::mappers::mapper::{Mapper, read_rom_bank};
+use crate::rom::cartridge::Cartridge;
+use crate::rom::rom_header::{LOGO_START, NINTENDO_LOGO};
+
+// The boot ROM reads the logo 0x30 times, the next read of 0x01xx unlocks the cartridge
+const UNLOCK_READS: u8 = 0x31;
+// While locked, A7 is forced high so the boot ROM sees the Nintendo logo stored at 0x0184
+const LOCKED_LOGO_OFFSET: u16 = 0x80;
+const CGB_FLAG_ADDRESS: u16 = 0x0143;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SachenVariant {
+    Mmc1,
+    // MMC2 has a second lock stage for the CGB boot ROM
+    Mmc2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LockState {
+    LockedDmg,
+    LockedCgb,
+    Unlocked,
+}
+
+/**
+ * Sachen boards hide the Nintendo logo by scrambling the address lines in 0x0100 - 0x01FF and
+ * redirecting logo reads while the boot ROM runs
+ */
+#[derive(Debug)]
+pub(crate) struct Sachen {
+    rom_data: Vec<u8>,
+    variant: SachenVariant,
+    // Bank mapped to 0x0000 - 0x3FFF, can only be changed while bits 4 and 5 are set
+    base_bank: u8,
+    // Bits set here are taken from the base bank instead of the ROM bank register
+    bank_mask: u8,
+    rom_bank: u8,
+    // Reads are counted to know when the boot ROM is done
+    lock_state: Cell<LockState>,
+    reads: Cell<u8>,
+}
+
+impl Mapper for Sachen {
+    fn new(cartridge: &Cartridge) -> Self {
+        // The header is scrambled too, so read the CGB flag the way the hardware would
+        let cgb_flag = cartridge
+            .get_data()
+            .get(unscramble_address(CGB_FLAG_ADDRESS) as usize)
+            .copied()
+            .unwrap_or(0);
+
+        let variant = if cgb_flag == 0x80 || cgb_flag == 0xC0 {
+            SachenVariant::Mmc2
+        } else {
+            SachenVariant::Mmc1
+        };
+
+        Sachen {
+            rom_data: cartridge.get_data().to_vec(),
+            variant,
+            base_bank: 0xFF,
+            bank_mask: 0x00,
+            rom_bank: 1,
+            lock_state: Cell::new(LockState::LockedDmg),
+            reads: Cell::new(0),
+        }
+    }
+
+    fn read(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..0x4000 => {
+                let addr = self.update_lock(addr);
+                let bank = self.base_bank & self.bank_mask;
+                read_rom_bank(&self.rom_data, bank as usize, addr as usize)
+            }
+            0x4000..0x8000 => {
+                let bank = (self.rom_bank & !self.bank_mask) | (self.base_bank & self.bank_mask);
+                read_rom_bank(&self.rom_data, bank as usize, addr as usize - 0x4000)
+            }
+            0xA000..0xC000 => 0xFF,
+            _ => panic!("Out of bank range"),
+        }
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        let is_unlocked = self.base_bank & 0x30 == 0x30;
+
+        match addr {
+            0x0000..0x2000 => {
+                if is_unlocked {
+                    self.base_bank = value;
+                }
+            }
+            0x2000..0x4000 => {
+                self.rom_bank = if value == 0 { 1 } else { value };
+            }
+            0x4000..0x6000 => {
+                if is_unlocked {
+                    self.bank_mask = value;
+                }
+            }
+            0x6000..0x8000 | 0xA000..0xC000 => {}
+            _ => panic!("Out of bank range"),
+        }
+    }
+
+    fn skip_boot(&mut self) {
+        // Nothing reads the logo, so the cartridge has to start unlocked
+        self.lock_state.set(LockState::Unlocked);
+        self.reads.set(0);
+    }
+}
+
+impl Sachen {
+    /**
+     * Count header reads while the boot ROM runs and return the address the ROM actually sees
+     */
+    fn update_lock(&self, addr: u16) -> u16 {
+        if addr & 0xFF00 != 0x0100 {
+            return addr;
+        }
+
+        let mut addr = addr;
+        let lock_state = self.lock_state.get();
+        if lock_state != LockState::Unlocked {
+            let reads = self.reads.get() + 1;
+            self.reads.set(reads);
+
+            // MMC1 redirects logo reads in the DMG stage, MMC2 only in the CGB stage
+            let is_redirected = match self.variant {
+                SachenVariant::Mmc1 => true,
+                SachenVariant::Mmc2 => lock_state == LockState::LockedCgb,
+            };
+
+            if reads == UNLOCK_READS {
+                self.reads.set(0);
+                self.lock_state.set(match (self.variant, lock_state) {
+                    (SachenVariant::Mmc2, LockState::LockedDmg) => LockState::LockedCgb,
+                    _ => LockState::Unlocked,
+                });
+            } else if is_redirected {
+                addr |= LOCKED_LOGO_OFFSET;
+            }
+        }
+
+        unscramble_address(addr)
+    }
+}
+
+// A0 and A6 are swapped, as are A1 and A4
+fn unscramble_address(addr: u16) -> u16 {
+    let mut unscrambled = addr & 0xFFAC;
+    unscrambled |= (addr & 0x40) >> 6;
+    unscrambled |= (addr & 0x10) >> 3;
+    unscrambled |= (addr & 0x02) << 3;
+    unscrambled |= (addr & 0x01) << 6;
+    unscrambled
+}
+
+/**
+ * Sachen carts have their own logo at 0x0104, the Nintendo logo is stored scrambled at 0x0184
+ */
+pub(crate) fn is_sachen(cartridge: &Cartridge) -> bool {
+    let rom_data = cartridge.get_data();
+    let logo_start = LOGO_START as u16 + LOCKED_LOGO_OFFSET;
+
+    NINTENDO_LOGO.iter().enumerate().all(|(i, byte)| {
+        let addr = unscramble_address(logo_start + i as u16) as usize;
+        rom_data.get(addr) == Some(byte)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::cpu::cpu::CPU;
+    use crate::mappers::mapper::get_mapper;
+    use crate::model::Model;
+
+    const ENTRY_POINT: u16 = 0x0100;
+    const ENTRY_BYTE: u8 = 0xAB;
+
+    fn sachen_cartridge() -> Cartridge {
+        let mut rom = vec![0; 0x10000];
+        let logo_start = LOGO_START as u16 + LOCKED_LOGO_OFFSET;
+        for (i, byte) in NINTENDO_LOGO.iter().enumerate() {
+            rom[unscramble_address(logo_start + i as u16) as usize] = *byte;
+        }
+        rom[unscramble_address(ENTRY_POINT) as usize] = ENTRY_BYTE;
+        Cartridge::from_data("sachen.gb".to_string(), rom, &Config::default()).unwrap()
+    }
+
+    #[test]
+    fn detects_scrambled_logo() {
+        assert!(is_sachen(&sachen_cartridge()));
+        assert!(format!("{:?}", get_mapper(&sachen_cartridge()).unwrap()).starts_with("Sachen"));
+    }
+
+    #[test]
+    fn shows_logo_to_boot_rom_then_unlocks() {
+        let sachen = Sachen::new(&sachen_cartridge());
+        for (i, byte) in NINTENDO_LOGO.iter().enumerate() {
+            assert_eq!(sachen.read(LOGO_START as u16 + i as u16), *byte);
+        }
+        assert_eq!(sachen.read(ENTRY_POINT), ENTRY_BYTE);
+    }
+
+    #[test]
+    fn boots_without_boot_rom() {
+        let cpu = CPU::new(&sachen_cartridge(), None, Model::Dmg).unwrap();
+        assert_eq!(cpu.bus.read_byte(ENTRY_POINT), ENTRY_BYTE);
+    }
+}
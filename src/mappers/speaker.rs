@@ -0,0 +1,16 @@
+use std::fmt;
+
+/**
+ * Output for the speaker on HuC3 cartridges, frontends can use this to play the tones games make
+ * with it
+ */
+pub trait TonePlayer {
+    // Called when the game starts a tone, the tone is a 4 bit number picked by the game
+    fn play_tone(&mut self, tone: u8);
+}
+
+impl fmt::Debug for dyn TonePlayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("TonePlayer")
+    }
+}
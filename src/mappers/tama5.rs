@@ -0,0 +1,369 @@
+use crate::mappers::mapper::{Mapper, load_ram, read_rom_bank};
+use crate::mappers::rtc::{RtcClock, RtcFooter, RtcMode, RtcRegisters};
+use crate::rom::cartridge::Cartridge;
+
+// Registers are written a nibble at a time, 0xA001 selects the register and 0xA000 holds the data
+const DATA_ADDRESS: usize = 0xA000;
+const SELECT_ADDRESS: usize = 0xA001;
+
+const ROM_BANK_LOW_REGISTER: u8 = 0x0;
+const ROM_BANK_HIGH_REGISTER: u8 = 0x1;
+const DATA_IN_LOW_REGISTER: u8 = 0x4;
+const DATA_IN_HIGH_REGISTER: u8 = 0x5;
+// Bit 0 is bit 4 of the address, bits 1 - 3 are the command
+const COMMAND_REGISTER: u8 = 0x6;
+// Writing the low nibble of the address executes the command
+const ADDRESS_LOW_REGISTER: u8 = 0x7;
+const STATUS_REGISTER: u8 = 0xA;
+const DATA_OUT_LOW_REGISTER: u8 = 0xC;
+const DATA_OUT_HIGH_REGISTER: u8 = 0xD;
+
+// Commands sent to the TAMA6 microcontroller
+const WRITE_MEMORY_COMMAND: u8 = 0x0;
+const READ_MEMORY_COMMAND: u8 = 0x1;
+const WRITE_RTC_COMMAND: u8 = 0x2;
+const READ_RTC_COMMAND: u8 = 0x3;
+
+// Clock registers, all in BCD
+const RTC_SECONDS: u8 = 0x0;
+const RTC_MINUTES: u8 = 0x1;
+const RTC_HOURS: u8 = 0x2;
+const RTC_DAYS_LOW: u8 = 0x3;
+const RTC_DAYS_HIGH: u8 = 0x4;
+
+// 32 bytes of EEPROM hold the save
+const MEMORY_SIZE: usize = 0x20;
+
+/**
+ * Bandai TAMA5, used by Tamagotchi 3. The game talks to a TAMA6 microcontroller with an EEPROM and
+ * a clock through nibble wide registers.
+ */
+#[derive(Debug)]
+pub(crate) struct Tama5 {
+    rom_data: Vec<u8>,
+    memory: [u8; MEMORY_SIZE],
+    // Set when the game writes to RAM, used to know when to save
+    is_ram_written: bool,
+    // The registers are only mapped after 0x0A is written to 0xA001
+    is_enabled: bool,
+    register: u8,
+    rom_bank: u8,
+    data_in: u8,
+    data_out: u8,
+    command: u8,
+    address_high: u8,
+
+    clock: RtcClock,
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    days: u16,
+}
+
+impl Mapper for Tama5 {
+    fn new(cartridge: &Cartridge) -> Self {
+        Tama5 {
+            rom_data: cartridge.get_data().to_vec(),
+            memory: [0; MEMORY_SIZE],
+            is_ram_written: false,
+            is_enabled: false,
+            register: 0,
+            rom_bank: 1,
+            data_in: 0,
+            data_out: 0,
+            command: 0,
+            address_high: 0,
+            clock: RtcClock::new(RtcMode::Emulated),
+            seconds: 0,
+            minutes: 0,
+            hours: 0,
+            days: 0,
+        }
+    }
+
+    fn read(&self, addr: u16) -> u8 {
+        let addr = addr as usize;
+
+        match addr {
+            0x0000..0x4000 => read_rom_bank(&self.rom_data, 0, addr),
+            0x4000..0x8000 => read_rom_bank(&self.rom_data, self.rom_bank as usize, addr - 0x4000),
+            DATA_ADDRESS => {
+                if !self.is_enabled {
+                    return 0xFF;
+                }
+
+                // Only the lower nibble is driven
+                let nibble = match self.register {
+                    // The microcontroller is always ready
+                    STATUS_REGISTER => 0x1,
+                    DATA_OUT_LOW_REGISTER => self.data_out & 0x0F,
+                    DATA_OUT_HIGH_REGISTER => self.data_out >> 4,
+                    _ => 0xF,
+                };
+                0xF0 | nibble
+            }
+            0xA001..0xC000 => 0xFF,
+            _ => panic!("Out of bank range"),
+        }
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        let addr = addr as usize;
+
+        match addr {
+            0x0000..0x8000 => {
+                // Banking is done through the TAMA5 registers
+            }
+            DATA_ADDRESS => {
+                if self.is_enabled {
+                    self.write_register(value & 0x0F);
+                }
+            }
+            SELECT_ADDRESS => {
+                let register = value & 0x0F;
+                if register == STATUS_REGISTER {
+                    self.is_enabled = true;
+                }
+                self.register = register;
+            }
+            0xA002..0xC000 => {}
+            _ => panic!("Out of bank range"),
+        }
+    }
+
+    fn step(&mut self, cycles: usize) {
+        let seconds = self.clock.step(cycles);
+        self.advance(seconds);
+    }
+
+    fn set_rtc_mode(&mut self, mode: RtcMode) {
+        let seconds = self.clock.set_mode(mode);
+        self.advance(seconds);
+    }
+
+    fn take_ram_written(&mut self) -> bool {
+        std::mem::take(&mut self.is_ram_written)
+    }
+
+    fn get_save_data(&self) -> Vec<u8> {
+        let mut data = self.memory.to_vec();
+        data.extend(self.get_footer().to_bytes());
+        data
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        let (memory, footer) = RtcFooter::split(data, MEMORY_SIZE);
+        load_ram(&mut self.memory, memory);
+        if let Some(footer) = footer {
+            self.load_footer(footer);
+        }
+    }
+}
+
+impl Tama5 {
+    /**
+     * The clock is saved in the same footer as MBC3 like SameBoy does, with the day counter split
+     * across the day registers. There is no latch, so the latched registers are a copy.
+     */
+    fn get_footer(&self) -> RtcFooter {
+        let registers = RtcRegisters {
+            seconds: self.seconds,
+            minutes: self.minutes,
+            hours: self.hours,
+            day_low: self.days as u8,
+            day_high: (self.days >> 8) as u8,
+        };
+
+        RtcFooter {
+            registers,
+            latched: registers,
+            timestamp: self.clock.get_timestamp(),
+        }
+    }
+
+    fn load_footer(&mut self, footer: RtcFooter) {
+        let registers = footer.registers;
+        self.seconds = registers.seconds % 60;
+        self.minutes = registers.minutes % 60;
+        self.hours = registers.hours % 24;
+        self.days = (((registers.day_high as u16) << 8) | registers.day_low as u16) % 10000;
+        self.advance(footer.elapsed());
+    }
+
+    fn write_register(&mut self, nibble: u8) {
+        match self.register {
+            ROM_BANK_LOW_REGISTER => self.rom_bank = (self.rom_bank & 0x10) | nibble,
+            ROM_BANK_HIGH_REGISTER => {
+                self.rom_bank = (self.rom_bank & 0x0F) | ((nibble & 0x01) << 4)
+            }
+            DATA_IN_LOW_REGISTER => self.data_in = (self.data_in & 0xF0) | nibble,
+            DATA_IN_HIGH_REGISTER => self.data_in = (self.data_in & 0x0F) | (nibble << 4),
+            COMMAND_REGISTER => {
+                self.address_high = nibble & 0x01;
+                self.command = nibble >> 1;
+            }
+            ADDRESS_LOW_REGISTER => {
+                let address = (self.address_high << 4) | nibble;
+                self.execute_command(address);
+            }
+            _ => {}
+        }
+    }
+
+    fn execute_command(&mut self, address: u8) {
+        let seconds = self.clock.sync();
+        self.advance(seconds);
+
+        match self.command {
+            WRITE_MEMORY_COMMAND => {
+                self.memory[address as usize] = self.data_in;
+                self.is_ram_written = true;
+            }
+            READ_MEMORY_COMMAND => self.data_out = self.memory[address as usize],
+            WRITE_RTC_COMMAND => self.write_rtc(address, self.data_in),
+            READ_RTC_COMMAND => self.data_out = self.read_rtc(address),
+            _ => {}
+        }
+    }
+
+    fn read_rtc(&self, register: u8) -> u8 {
+        match register {
+            RTC_SECONDS => to_bcd(self.seconds),
+            RTC_MINUTES => to_bcd(self.minutes),
+            RTC_HOURS => to_bcd(self.hours),
+            RTC_DAYS_LOW => to_bcd((self.days % 100) as u8),
+            RTC_DAYS_HIGH => to_bcd((self.days / 100) as u8),
+            _ => 0x00,
+        }
+    }
+
+    fn write_rtc(&mut self, register: u8, value: u8) {
+        let value = from_bcd(value);
+
+        match register {
+            RTC_SECONDS => {
+                self.seconds = value % 60;
+                self.clock.reset_sub_second();
+            }
+            RTC_MINUTES => self.minutes = value % 60,
+            RTC_HOURS => self.hours = value % 24,
+            RTC_DAYS_LOW => self.days = (self.days / 100) * 100 + value as u16,
+            RTC_DAYS_HIGH => self.days = (value as u16 % 100) * 100 + self.days % 100,
+            _ => {}
+        }
+    }
+
+    fn advance(&mut self, seconds: u64) {
+        let seconds = self.seconds as u64 + seconds;
+        self.seconds = (seconds % 60) as u8;
+        let minutes = self.minutes as u64 + seconds / 60;
+        self.minutes = (minutes % 60) as u8;
+        let hours = self.hours as u64 + minutes / 60;
+        self.hours = (hours % 24) as u8;
+        // 4 BCD digits of days
+        self.days = ((self.days as u64 + hours / 24) % 10000) as u16;
+    }
+}
+
+fn to_bcd(value: u8) -> u8 {
+    ((value / 10) << 4) | (value % 10)
+}
+
+fn from_bcd(value: u8) -> u8 {
+    (value >> 4) * 10 + (value & 0x0F)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn tama5_cartridge() -> Cartridge {
+        let mut rom = vec![0; 0x20000];
+        rom[0x0147] = 0xFD;
+        Cartridge::from_data("tama5.gb".to_string(), rom, &Config::default()).unwrap()
+    }
+
+    #[test]
+    fn saves_memory_and_clock() {
+        let mut tama5 = Tama5::new(&tama5_cartridge());
+        tama5.memory[3] = 0x42;
+        tama5.minutes = 34;
+        tama5.hours = 12;
+        tama5.days = 1234;
+
+        let data = tama5.get_save_data();
+        assert_eq!(data.len(), MEMORY_SIZE + 48);
+
+        let mut loaded = Tama5::new(&tama5_cartridge());
+        loaded.load_save_data(&data);
+        assert_eq!(loaded.memory[3], 0x42);
+        assert_eq!(loaded.minutes, 34);
+        assert_eq!(loaded.hours, 12);
+        assert_eq!(loaded.days, 1234);
+    }
+
+    #[test]
+    fn loads_saves_without_clock() {
+        let mut tama5 = Tama5::new(&tama5_cartridge());
+        tama5.load_save_data(&[0x07; MEMORY_SIZE]);
+        assert_eq!(tama5.memory, [0x07; MEMORY_SIZE]);
+        assert_eq!(tama5.days, 0);
+    }
+
+    #[test]
+    fn clock_registers_are_bcd() {
+        let mut tama5 = Tama5::new(&tama5_cartridge());
+        tama5.write_rtc(RTC_MINUTES, 0x59);
+        tama5.advance(60);
+        assert_eq!(tama5.read_rtc(RTC_MINUTES), 0x00);
+        assert_eq!(tama5.read_rtc(RTC_HOURS), 0x01);
+    }
+
+    fn write_register(tama5: &mut Tama5, register: u8, nibble: u8) {
+        tama5.write(SELECT_ADDRESS as u16, register);
+        tama5.write(DATA_ADDRESS as u16, nibble);
+    }
+
+    #[test]
+    fn registers_need_enabling() {
+        let mut tama5 = Tama5::new(&tama5_cartridge());
+        assert_eq!(tama5.read(DATA_ADDRESS as u16), 0xFF);
+
+        tama5.write(SELECT_ADDRESS as u16, STATUS_REGISTER);
+        assert_eq!(tama5.read(DATA_ADDRESS as u16), 0xF1);
+
+        write_register(&mut tama5, ROM_BANK_LOW_REGISTER, 0x3);
+        write_register(&mut tama5, ROM_BANK_HIGH_REGISTER, 0x1);
+        assert_eq!(tama5.rom_bank, 0x13);
+    }
+
+    #[test]
+    fn memory_is_written_and_read_through_commands() {
+        let mut tama5 = Tama5::new(&tama5_cartridge());
+        tama5.write(SELECT_ADDRESS as u16, STATUS_REGISTER);
+
+        write_register(&mut tama5, DATA_IN_LOW_REGISTER, 0x4);
+        write_register(&mut tama5, DATA_IN_HIGH_REGISTER, 0x2);
+        write_register(
+            &mut tama5,
+            COMMAND_REGISTER,
+            (WRITE_MEMORY_COMMAND << 1) | 0x1,
+        );
+        write_register(&mut tama5, ADDRESS_LOW_REGISTER, 0x2);
+        assert_eq!(tama5.memory[0x12], 0x24);
+        assert!(tama5.take_ram_written());
+
+        write_register(
+            &mut tama5,
+            COMMAND_REGISTER,
+            (READ_MEMORY_COMMAND << 1) | 0x1,
+        );
+        write_register(&mut tama5, ADDRESS_LOW_REGISTER, 0x2);
+        tama5.write(SELECT_ADDRESS as u16, DATA_OUT_LOW_REGISTER);
+        assert_eq!(tama5.read(DATA_ADDRESS as u16), 0xF4);
+        tama5.write(SELECT_ADDRESS as u16, DATA_OUT_HIGH_REGISTER);
+        assert_eq!(tama5.read(DATA_ADDRESS as u16), 0xF2);
+        assert!(!tama5.take_ram_written());
+    }
+}
@@ -0,0 +1,96 @@
+use crate::mappers::mapper::{Mapper, ROM_BANK_SIZE, read_rom_bank};
+use crate::rom::cartridge::Cartridge;
+
+// Bigger than a ROM only cartridge can address
+const ROM_ONLY_SIZE: usize = 0x8000;
+
+/**
+ * Wisdom Tree carts switch the whole 32KiB ROM area at once, the bank is the low byte of the
+ * address written to in 0x0000 - 0x3FFF, the value is ignored
+ */
+#[derive(Debug)]
+pub(crate) struct WisdomTree {
+    rom_data: Vec<u8>,
+    // 32KiB bank
+    rom_bank: u8,
+}
+
+impl Mapper for WisdomTree {
+    fn new(cartridge: &Cartridge) -> Self {
+        WisdomTree {
+            rom_data: cartridge.get_data().to_vec(),
+            rom_bank: 0,
+        }
+    }
+
+    fn read(&self, addr: u16) -> u8 {
+        let addr = addr as usize;
+
+        match addr {
+            0x0000..0x8000 => {
+                let bank = self.rom_bank as usize * 2 + addr / ROM_BANK_SIZE;
+                read_rom_bank(&self.rom_data, bank, addr % ROM_BANK_SIZE)
+            }
+            0xA000..0xC000 => 0xFF,
+            _ => panic!("Out of bank range"),
+        }
+    }
+
+    fn write(&mut self, addr: u16, _value: u8) {
+        let addr = addr as usize;
+
+        match addr {
+            0x0000..0x4000 => self.rom_bank = addr as u8,
+            0x4000..0x8000 | 0xA000..0xC000 => {}
+            _ => panic!("Out of bank range"),
+        }
+    }
+}
+
+/**
+ * Wisdom Tree headers claim to be ROM only, but the ROM is too big for that and the company name
+ * is in the first bank
+ */
+pub(crate) fn is_wisdom_tree(cartridge: &Cartridge) -> bool {
+    let rom_data = cartridge.get_data();
+    if cartridge.rom_header.cartridge_type != 0x00 || rom_data.len() <= ROM_ONLY_SIZE {
+        return false;
+    }
+
+    let first_bank = &rom_data[..ROM_BANK_SIZE.min(rom_data.len())];
+    [&b"WISDOM TREE"[..], &b"WISDOM\0TREE"[..]]
+        .iter()
+        .any(|name| first_bank.windows(name.len()).any(|window| window == *name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    // Each 32KiB bank has its number at 0x0200
+    fn wisdom_tree_cartridge(bank_count: usize) -> Cartridge {
+        let mut rom = vec![0; bank_count * ROM_ONLY_SIZE];
+        for bank in 0..bank_count {
+            rom[bank * ROM_ONLY_SIZE + 0x0200] = bank as u8;
+        }
+        rom[0x1000..0x100B].copy_from_slice(b"WISDOM TREE");
+        Cartridge::from_data("wisdom_tree.gb".to_string(), rom, &Config::default()).unwrap()
+    }
+
+    #[test]
+    fn detects_company_name() {
+        assert!(is_wisdom_tree(&wisdom_tree_cartridge(4)));
+        // A ROM only cart that fits in 32KiB is just a ROM only cart
+        assert!(!is_wisdom_tree(&wisdom_tree_cartridge(1)));
+    }
+
+    #[test]
+    fn switches_banks_by_address() {
+        let mut wisdom_tree = WisdomTree::new(&wisdom_tree_cartridge(4));
+        assert_eq!(wisdom_tree.read(0x0200), 0);
+
+        wisdom_tree.write(0x0003, 0x00);
+        assert_eq!(wisdom_tree.read(0x0200), 3);
+    }
+}
@@ -0,0 +1,91 @@
+use std::str::FromStr;
+
+use serde::Deserialize;
+use serde::de::{IntoDeserializer, value};
+
+use crate::rom::rom_header::{CgbFlag, OldLicenseCode, RomHeader};
+
+/**
+ * The console being emulated. Revisions differ in their boot ROM, the state it leaves the CPU in
+ * and whether CGB features are there.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Model {
+    // The first DMG revision, with a different boot ROM
+    Dmg0,
+    Dmg,
+    // Game Boy Pocket and Light
+    Mgb,
+    // Super Game Boy
+    Sgb,
+    Sgb2,
+    Cgb,
+    // Game Boy Advance, a CGB with a slightly different boot ROM
+    Agb,
+}
+
+impl Model {
+    /**
+     * Pick the console a cartridge was made for, from the CGB and SGB flags in its header
+     */
+    pub fn detect(header: &RomHeader) -> Self {
+        let is_sgb_supported =
+            header.sgb_flag && header.old_license_code == OldLicenseCode::NewLicenseCode;
+
+        match header.cgb_flag {
+            CgbFlag::Compatible | CgbFlag::CgbOnly => Model::Cgb,
+            CgbFlag::DmgOnly if is_sgb_supported => Model::Sgb,
+            CgbFlag::DmgOnly => Model::Dmg,
+        }
+    }
+
+    pub fn is_cgb(&self) -> bool {
+        matches!(self, Model::Cgb | Model::Agb)
+    }
+
+    pub fn is_sgb(&self) -> bool {
+        matches!(self, Model::Sgb | Model::Sgb2)
+    }
+}
+
+/**
+ * Parsed with the same names as the config, so the command line can't drift from it
+ */
+impl FromStr for Model {
+    type Err = value::Error;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Model::deserialize(name.into_deserializer())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_config_names() {
+        assert_eq!("dmg0".parse::<Model>().unwrap(), Model::Dmg0);
+        assert_eq!("sgb2".parse::<Model>().unwrap(), Model::Sgb2);
+        assert_eq!("agb".parse::<Model>().unwrap(), Model::Agb);
+        assert!("gba".parse::<Model>().is_err());
+        assert!("DMG".parse::<Model>().is_err());
+    }
+
+    #[test]
+    fn detects_model_from_header() {
+        let mut rom = vec![0; 0x8000];
+        let header = |rom: &Vec<u8>| RomHeader::parse(rom).unwrap();
+        assert_eq!(Model::detect(&header(&rom)), Model::Dmg);
+
+        // SGB support needs the new licensee code too
+        rom[0x0146] = 0x03;
+        assert_eq!(Model::detect(&header(&rom)), Model::Dmg);
+        rom[0x014B] = 0x33;
+        assert_eq!(Model::detect(&header(&rom)), Model::Sgb);
+
+        rom[0x0143] = 0x80;
+        assert_eq!(Model::detect(&header(&rom)), Model::Cgb);
+    }
+}
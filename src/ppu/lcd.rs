@@ -3,7 +3,8 @@ use crate::{
         bus::Bus,
         interrupt_flags::{self, InterruptType},
     },
-    ppu::ppu::PPU,
+    model::Model,
+    ppu::{palette, ppu::PPU},
     utils::test_bit,
 };
 
@@ -75,11 +76,18 @@ pub struct Lcd {
 }
 
 impl Lcd {
-    pub fn new() -> Self {
-        Lcd {
-            cycles: 0,
-            ppu: PPU::new(),
-        }
+    pub fn new(model: Model) -> Self {
+        let mut ppu = PPU::new();
+        ppu.set_palette(palette::get_default_palette(model));
+
+        Lcd { cycles: 0, ppu }
+    }
+
+    /**
+     * Set the colours the 4 shades are drawn with
+     */
+    pub fn set_palette(&mut self, palette: [[u8; 4]; 4]) {
+        self.ppu.set_palette(palette);
     }
 
     pub fn update_graphics(&mut self, bus: &mut Bus, cycles: usize) -> Option<[u8; BUFFER_SIZE]> {
@@ -1,3 +1,5 @@
+use crate::model::Model;
+
 pub const SYSTEM_PALETTE: [[u8; 4]; 4] = [
     // RGBA values
     [255, 255, 255, 255],
@@ -5,3 +7,22 @@ pub const SYSTEM_PALETTE: [[u8; 4]; 4] = [
     [64, 64, 64, 255],
     [0, 0, 0, 255],
 ];
+
+// Palette 1-A, the one the SGB shows games without SGB palettes in
+pub const SGB_PALETTE: [[u8; 4]; 4] = [
+    [248, 232, 200, 255],
+    [216, 144, 72, 255],
+    [168, 40, 32, 255],
+    [48, 24, 80, 255],
+];
+
+/**
+ * Colours the model shows DMG games in when the config doesn't set a palette
+ */
+pub fn get_default_palette(model: Model) -> [[u8; 4]; 4] {
+    if model.is_sgb() {
+        SGB_PALETTE
+    } else {
+        SYSTEM_PALETTE
+    }
+}
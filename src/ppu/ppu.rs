@@ -9,6 +9,7 @@ use crate::{
         sprite,
         tile::Tile,
     },
+    utils::test_bit,
 };
 
 use std::collections::HashMap;
@@ -23,12 +24,56 @@ const LAYER_WIDTH: usize = BACKGROUND_SIZE / TILE_SIZE;
 
 const BG_TILE_DATA_AREA_1_BASE_POINTER: u16 = 0x9000;
 
+// Bits in the CGB background attributes, stored in VRAM bank 1 at the same address as the index
+const ATTRIBUTE_BANK_BIT: u8 = 3;
+const ATTRIBUTE_X_FLIP_BIT: u8 = 5;
+const ATTRIBUTE_Y_FLIP_BIT: u8 = 6;
+
+/**
+ * A tile map entry, the attributes are always 0 outside of CGB mode
+ */
+#[derive(Debug, Clone, Copy, Default)]
+struct MapEntry {
+    tile_index: u8,
+    attributes: u8,
+}
+
+impl MapEntry {
+    fn get_bank(&self) -> usize {
+        test_bit(self.attributes, ATTRIBUTE_BANK_BIT) as usize
+    }
+
+    // Flip the pixel coordinates within the tile
+    fn get_pixel(&self, x: usize, y: usize) -> (usize, usize) {
+        let x = if test_bit(self.attributes, ATTRIBUTE_X_FLIP_BIT) {
+            TILE_SIZE - 1 - x
+        } else {
+            x
+        };
+        let y = if test_bit(self.attributes, ATTRIBUTE_Y_FLIP_BIT) {
+            TILE_SIZE - 1 - y
+        } else {
+            y
+        };
+        (x, y)
+    }
+}
+
 #[derive(Debug)]
-pub struct PPU {}
+pub struct PPU {
+    // RGBA colour of each shade, from lightest to darkest
+    palette: [[u8; 4]; 4],
+}
 
 impl PPU {
     pub fn new() -> Self {
-        PPU {}
+        PPU {
+            palette: SYSTEM_PALETTE,
+        }
+    }
+
+    pub fn set_palette(&mut self, palette: [[u8; 4]; 4]) {
+        self.palette = palette;
     }
 
     pub fn render(&self, bus: &Bus, lcd: &Lcd) -> [u8; BUFFER_SIZE] {
@@ -60,16 +105,16 @@ impl PPU {
             for px in 0..SCREEN_WIDTH as usize {
                 let x = (px + x_offset as usize) % BACKGROUND_SIZE;
                 let map_num = (y / TILE_SIZE) * LAYER_WIDTH + (x / TILE_SIZE);
-                let tile_index = tile_map[map_num];
+                let entry = tile_map[map_num];
                 // we should have the tile, otherwise something went wrong, so safe to unwrap here and crash
-                let tile = tile_set.get(&tile_index).unwrap();
+                let tile = tile_set.get(&(entry.tile_index, entry.get_bank())).unwrap();
 
-                let tile_index_x = x % TILE_SIZE;
+                let (tile_index_x, tile_index_y) = entry.get_pixel(x % TILE_SIZE, tile_index_y);
                 let row = tile.get_row(tile_index_y);
 
                 let value = row[tile_index_x];
                 let palette_index = palette[value as usize];
-                let colour = SYSTEM_PALETTE[palette_index as usize];
+                let colour = self.palette[palette_index as usize];
                 self.copy_colour_into_buffer(buffer, &colour, px, py);
             }
         }
@@ -89,16 +134,16 @@ impl PPU {
             let tile_index_y = py % TILE_SIZE;
 
             for px in (x_offset as usize)..(SCREEN_WIDTH as usize) {
-                let tile_index_x = px % TILE_SIZE;
                 let map_num = (py / TILE_SIZE) * LAYER_WIDTH + (px / TILE_SIZE);
-                let tile_index = tile_map[map_num];
-                let tile = tile_set.get(&tile_index).unwrap();
+                let entry = tile_map[map_num];
+                let tile = tile_set.get(&(entry.tile_index, entry.get_bank())).unwrap();
 
+                let (tile_index_x, tile_index_y) = entry.get_pixel(px % TILE_SIZE, tile_index_y);
                 let row = tile.get_row(tile_index_y);
 
                 let value = row[tile_index_x];
                 let palette_index = palette[value as usize];
-                let colour = SYSTEM_PALETTE[palette_index as usize];
+                let colour = self.palette[palette_index as usize];
                 self.copy_colour_into_buffer(buffer, &colour, px, py);
             }
         }
@@ -118,7 +163,8 @@ impl PPU {
             let addr = BG_TILE_DATA_AREA_START_BANK_0
                 + (BYTES_PER_TILE * (sprite.get_tile_index() as u16));
 
-            let tile = Tile::new(bus, addr);
+            // CGB sprites from VRAM bank 1 aren't drawn yet
+            let tile = Tile::new(bus, addr, 0);
             let palette = lcd.get_sprite_palette(bus, sprite.get_attributes().get_dmg_palette());
 
             // paint the tile on the buffer
@@ -161,7 +207,7 @@ impl PPU {
                     if attributes.is_low_priority()
                         && !self.does_current_colour_equal(
                             buffer,
-                            &SYSTEM_PALETTE[0], // white
+                            &self.palette[0], // white
                             x_cord,
                             y_cord,
                         )
@@ -170,7 +216,7 @@ impl PPU {
                     }
 
                     let palette_index = palette[value as usize];
-                    let colour = SYSTEM_PALETTE[palette_index as usize];
+                    let colour = self.palette[palette_index as usize];
                     self.copy_colour_into_buffer(buffer, &colour, x_cord, y_cord);
                 }
             }
@@ -221,8 +267,8 @@ impl PPU {
         &self,
         bus: &Bus,
         lcd: &Lcd,
-        tile_map: &[u8],
-    ) -> HashMap<u8, Tile> {
+        tile_map: &[MapEntry],
+    ) -> HashMap<(u8, usize), Tile> {
         // Get the tiles we need from memory and parse them, store them in the set
         let mut tile_set = HashMap::new();
         let tile_data_area_start = lcd.get_bg_window_tile_data_area_start(bus);
@@ -230,8 +276,10 @@ impl PPU {
 
         for i in 0..TILE_MAP_AREA_SIZE {
             // Loop through the tile map, if we haven't already parsed the tile, grab it from memory and parse it
-            let tile_index = tile_map[i];
-            if tile_set.contains_key(&tile_index) {
+            let entry = tile_map[i];
+            let tile_index = entry.tile_index;
+            let key = (tile_index, entry.get_bank());
+            if tile_set.contains_key(&key) {
                 continue;
             }
 
@@ -245,36 +293,101 @@ impl PPU {
                 tile_data_area_start + memory_index
             };
 
-            let tile = Tile::new(bus, addr);
-            tile_set.insert(tile_index, tile);
+            let tile = Tile::new(bus, addr, entry.get_bank());
+            tile_set.insert(key, tile);
         }
 
         return tile_set;
     }
 
-    fn get_background_tile_map(&self, bus: &Bus, lcd: &Lcd) -> [u8; TILE_MAP_AREA_SIZE] {
+    fn get_background_tile_map(&self, bus: &Bus, lcd: &Lcd) -> [MapEntry; TILE_MAP_AREA_SIZE] {
         self.get_tile_map(bus, lcd, true)
     }
 
-    fn get_window_tile_map(&self, bus: &Bus, lcd: &Lcd) -> [u8; TILE_MAP_AREA_SIZE] {
+    fn get_window_tile_map(&self, bus: &Bus, lcd: &Lcd) -> [MapEntry; TILE_MAP_AREA_SIZE] {
         self.get_tile_map(bus, lcd, false)
     }
 
-    fn get_tile_map(&self, bus: &Bus, lcd: &Lcd, is_background: bool) -> [u8; TILE_MAP_AREA_SIZE] {
+    fn get_tile_map(
+        &self,
+        bus: &Bus,
+        lcd: &Lcd,
+        is_background: bool,
+    ) -> [MapEntry; TILE_MAP_AREA_SIZE] {
         let tile_map_start = if is_background {
             lcd.get_bg_tile_map_area_start(bus)
         } else {
             lcd.get_window_tile_map_area_start(bus)
         };
         // Tile map stores the index of the tile to be displayed
-        let mut tile_map = [0; TILE_MAP_AREA_SIZE];
+        let mut tile_map = [MapEntry::default(); TILE_MAP_AREA_SIZE];
 
         for i in 0..TILE_MAP_AREA_SIZE as u16 {
             let addr = tile_map_start + i;
-            let tile_index = bus.read_byte(addr);
-            tile_map[i as usize] = tile_index;
+            // Bank 1 holds the CGB tile attributes, the indexes are in bank 0
+            tile_map[i as usize] = MapEntry {
+                tile_index: bus.read_vram(addr, 0),
+                attributes: if bus.is_cgb_mode() {
+                    bus.read_vram(addr, 1)
+                } else {
+                    0
+                },
+            };
         }
 
         return tile_map;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::model::Model;
+    use crate::rom::cartridge::Cartridge;
+
+    #[test]
+    fn map_entry_flips_pixels() {
+        let entry = MapEntry {
+            tile_index: 0,
+            attributes: 0x08,
+        };
+        assert_eq!(entry.get_bank(), 1);
+        assert_eq!(entry.get_pixel(1, 2), (1, 2));
+
+        let entry = MapEntry {
+            tile_index: 0,
+            attributes: 0x60,
+        };
+        assert_eq!(entry.get_bank(), 0);
+        assert_eq!(entry.get_pixel(1, 2), (6, 5));
+    }
+
+    #[test]
+    fn background_tiles_come_from_their_bank() {
+        let mut rom = vec![0; 0x8000];
+        // CGB compatible
+        rom[0x0143] = 0x80;
+        let cartridge =
+            Cartridge::from_data("cgb.gbc".to_string(), rom, &Config::default()).unwrap();
+        let mut bus = Bus::new(&cartridge, None, Model::Cgb).unwrap();
+        // LCD and background on, tiles at 0x8000, identity palette
+        bus.write_byte(0xFF40, 0x91);
+        bus.write_byte(0xFF47, 0xE4);
+
+        // Tile 0 in bank 1 is solid colour 3, the first map entry uses bank 1
+        bus.write_byte(0xFF4F, 1);
+        for addr in 0x8000..0x8010 {
+            bus.write_byte(addr, 0xFF);
+        }
+        bus.write_byte(0x9800, 0x08);
+        bus.write_byte(0xFF4F, 0);
+
+        let ppu = PPU::new();
+        let buffer = ppu.render(&bus, &Lcd::new(Model::Cgb));
+        assert_eq!(buffer[0..4], SYSTEM_PALETTE[3]);
+        // The next entry uses tile 0 from bank 0
+        let next_tile = TILE_SIZE * 4;
+        assert_eq!(buffer[next_tile..next_tile + 4], SYSTEM_PALETTE[0]);
+    }
+}
@@ -8,13 +8,16 @@ pub struct Tile {
 }
 
 impl Tile {
-    pub fn new(bus: &Bus, addr: u16) -> Tile {
+    /**
+     * Read a tile from a VRAM bank, bank 1 only has tiles in CGB mode
+     */
+    pub fn new(bus: &Bus, addr: u16, bank: usize) -> Tile {
         Tile {
-            pixels: Self::read(bus, addr),
+            pixels: Self::read(bus, addr, bank),
         }
     }
 
-    fn read(bus: &Bus, addr: u16) -> [[u8; 8]; 8] {
+    fn read(bus: &Bus, addr: u16, bank: usize) -> [[u8; 8]; 8] {
         let mut pixels = [[0; 8]; 8];
         // this needs to be changed to skip by 2
         for i in (0..BYTES_PER_TILE).step_by(2) {
@@ -23,8 +26,8 @@ impl Tile {
             let row = (i / 2) as usize;
 
             let addr = addr + i;
-            let least_significant_byte = bus.read_byte(addr);
-            let most_significant_byte = bus.read_byte(addr + 1);
+            let least_significant_byte = bus.read_vram(addr, bank);
+            let most_significant_byte = bus.read_vram(addr + 1, bank);
 
             // bits are flipped around, the most significant bit (left most) represents the
             // right most bit and vice versa
@@ -0,0 +1,172 @@
+use std::fs;
+use std::io::{self, Cursor, Read};
+use std::path::Path;
+
+use flate2::read::GzDecoder;
+use zip::ZipArchive;
+
+const ZIP_MAGIC: [u8; 4] = [0x50, 0x4B, 0x03, 0x04];
+const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+
+const ROM_EXTENSIONS: [&str; 3] = ["gb", "gbc", "sgb"];
+
+/**
+ * A ROM read from disk, possibly out of an archive
+ */
+#[derive(Debug)]
+pub struct RomFile {
+    pub data: Vec<u8>,
+    // Where the ROM would be if it was extracted next to the archive, saves are named after it
+    pub path: String,
+}
+
+/**
+ * Read a ROM, or the first ROM in a .zip or .gz archive. A zip entry can be picked by name.
+ */
+pub fn read_rom(path: &str, entry: Option<&str>) -> io::Result<RomFile> {
+    let data = fs::read(path)?;
+
+    if data.starts_with(&ZIP_MAGIC) {
+        return read_zip(path, data, entry);
+    }
+
+    if entry.is_some() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{} is not a zip archive, it has no entries to pick from",
+                path
+            ),
+        ));
+    }
+
+    if data.starts_with(&GZIP_MAGIC) {
+        let mut rom = Vec::new();
+        GzDecoder::new(data.as_slice()).read_to_end(&mut rom)?;
+        // foo.gb.gz holds foo.gb
+        let inner_path = Path::new(path).with_extension("");
+        return Ok(RomFile {
+            data: rom,
+            path: inner_path.to_string_lossy().to_string(),
+        });
+    }
+
+    Ok(RomFile {
+        data,
+        path: path.to_string(),
+    })
+}
+
+fn read_zip(path: &str, data: Vec<u8>, entry: Option<&str>) -> io::Result<RomFile> {
+    let mut archive = ZipArchive::new(Cursor::new(data))?;
+
+    let index = (0..archive.len())
+        .find(|&i| {
+            archive
+                .name_for_index(i)
+                .is_some_and(|name| is_matching_entry(name, entry))
+        })
+        .ok_or_else(|| {
+            let message = match entry {
+                Some(entry) => format!("{} has no entry named {}", path, entry),
+                None => format!("{} has no .gb, .gbc or .sgb file", path),
+            };
+            io::Error::new(io::ErrorKind::NotFound, message)
+        })?;
+
+    let mut file = archive.by_index(index)?;
+    let mut rom = Vec::with_capacity(file.size() as usize);
+    file.read_to_end(&mut rom)?;
+
+    // Entries can be in folders inside the archive, only the file name is kept
+    let file_name = Path::new(file.name())
+        .file_name()
+        .map(|name| name.to_os_string())
+        .unwrap_or_default();
+    let inner_path = Path::new(path).with_file_name(file_name);
+
+    Ok(RomFile {
+        data: rom,
+        path: inner_path.to_string_lossy().to_string(),
+    })
+}
+
+fn is_matching_entry(name: &str, entry: Option<&str>) -> bool {
+    let entry_path = Path::new(name);
+
+    match entry {
+        Some(entry) => name == entry || entry_path.file_name().is_some_and(|file| file == entry),
+        None => entry_path.extension().is_some_and(|extension| {
+            ROM_EXTENSIONS
+                .iter()
+                .any(|rom_extension| extension.eq_ignore_ascii_case(rom_extension))
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+    use zip::ZipWriter;
+    use zip::write::SimpleFileOptions;
+
+    fn write_zip(path: &Path, entries: &[(&str, &[u8])]) {
+        let mut zip = ZipWriter::new(fs::File::create(path).unwrap());
+        for (name, data) in entries {
+            zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn reads_first_rom_in_zip() {
+        let path = std::env::temp_dir().join("archive_first_rom.zip");
+        write_zip(
+            &path,
+            &[("readme.txt", b"hi"), ("games/foo.gbc", &[1, 2, 3])],
+        );
+
+        let rom = read_rom(path.to_str().unwrap(), None).unwrap();
+        assert_eq!(rom.data, [1, 2, 3]);
+        assert_eq!(
+            rom.path,
+            std::env::temp_dir().join("foo.gbc").to_str().unwrap()
+        );
+
+        let rom = read_rom(path.to_str().unwrap(), Some("readme.txt")).unwrap();
+        assert_eq!(rom.data, b"hi");
+
+        let error = read_rom(path.to_str().unwrap(), Some("bar.gb")).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn zip_without_rom_is_an_error() {
+        let path = std::env::temp_dir().join("archive_no_rom.zip");
+        write_zip(&path, &[("readme.txt", b"hi")]);
+
+        let error = read_rom(path.to_str().unwrap(), None).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn reads_gzip() {
+        let path = std::env::temp_dir().join("archive_rom.gb.gz");
+        let mut encoder = GzEncoder::new(fs::File::create(&path).unwrap(), Compression::default());
+        encoder.write_all(&[4, 5, 6]).unwrap();
+        encoder.finish().unwrap();
+
+        let rom = read_rom(path.to_str().unwrap(), None).unwrap();
+        let error = read_rom(path.to_str().unwrap(), Some("rom.gb")).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(rom.data, [4, 5, 6]);
+        assert!(rom.path.ends_with("archive_rom.gb"));
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,77 @@
+use std::fs;
+use std::io;
+
+pub(crate) const BOOT_ROM_SIZE: usize = 0x100;
+// The CGB boot ROM also covers 0x0200 - 0x08FF
+const CGB_BOOT_ROM_SIZE: usize = 0x900;
+
+/**
+ * A dump of the DMG or MGB boot ROM, it is mapped over the start of the cartridge until the game
+ * is started
+ */
+#[derive(Debug, Clone)]
+pub struct BootRom {
+    data: [u8; BOOT_ROM_SIZE],
+}
+
+impl BootRom {
+    pub fn load(path: &str) -> io::Result<Self> {
+        let data = fs::read(path)?;
+
+        if data.len() == CGB_BOOT_ROM_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "The boot ROM {} is a CGB boot ROM, only DMG and MGB boot ROMs are supported",
+                    path
+                ),
+            ));
+        }
+
+        let data = data.try_into().map_err(|data: Vec<u8>| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "The boot ROM {} is {} bytes, DMG and MGB boot ROMs are {} bytes",
+                    path,
+                    data.len(),
+                    BOOT_ROM_SIZE
+                ),
+            )
+        })?;
+
+        Ok(BootRom { data })
+    }
+
+    pub fn read(&self, addr: u16) -> u8 {
+        self.data[addr as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_boot_rom(name: &str, size: usize) -> io::Result<BootRom> {
+        let path = std::env::temp_dir().join(name);
+        fs::write(&path, vec![0x31; size])?;
+        let boot_rom = BootRom::load(path.to_str().unwrap());
+        fs::remove_file(&path)?;
+        boot_rom
+    }
+
+    #[test]
+    fn loads_dmg_boot_rom() {
+        let boot_rom = load_boot_rom("boot_rom_dmg.bin", BOOT_ROM_SIZE).unwrap();
+        assert_eq!(boot_rom.read(0x00FF), 0x31);
+    }
+
+    #[test]
+    fn rejects_other_sizes() {
+        let error = load_boot_rom("boot_rom_cgb.bin", CGB_BOOT_ROM_SIZE).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::Unsupported);
+
+        let error = load_boot_rom("boot_rom_short.bin", 0x80).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
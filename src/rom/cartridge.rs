@@ -1,47 +1,134 @@
-use crate::rom::rom_header::RomHeader;
-
-use core::panic;
-use std::fs;
-use std::io;
-
-#[derive(Debug)]
-pub struct Cartridge {
-    file_name: String,
-    rom_size: u32,
-    rom_data: Vec<u8>,
-    pub rom_header: RomHeader,
-}
-
-impl Cartridge {
-    pub fn new(path: &str) -> io::Result<Self> {
-        let rom_data = fs::read(path)?;
-        let rom_size = rom_data.len() as u32;
-
-        let rom_header = RomHeader::parse(&rom_data)?;
-
-        Ok(Self {
-            file_name: path.to_string(),
-            rom_size,
-            rom_data,
-            rom_header,
-        })
-    }
-
-    pub fn validate_header_checksum(&self) -> bool {
-        let mut computed_checksum: u8 = 0;
-
-        for i in 0x0134..(0x014C + 1) {
-            computed_checksum = computed_checksum.wrapping_sub(self.rom_data[i] + 1);
-        }
-
-        if computed_checksum != self.rom_header.header_checksum {
-            panic!("Header checksum is invalid!!!");
-        }
-
-        return true;
-    }
-
-    pub fn get_data(&self) -> &Vec<u8> {
-        return &self.rom_data;
-    }
-}
+use crate::config::{Config, GameConfig, MapperOverride};
+use crate::rom::archive;
+use crate::rom::patch::{self, PATCH_EXTENSIONS};
+use crate::rom::rom_header::{CartridgeType, RomHeader};
+use crate::rom::validation::ValidationReport;
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/**
+ * Where to find the ROM when the file holds more than a ROM, and what to patch it with
+ */
+#[derive(Debug, Default)]
+pub struct LoadOptions {
+    // File to load from a zip archive, the first ROM in the archive is used if None
+    pub archive_entry: Option<String>,
+    // IPS, UPS or BPS patch, a patch with the same name as the ROM is used if None
+    pub patch: Option<String>,
+    // Don't look for a patch next to the ROM
+    pub skip_patch_search: bool,
+    // Global and per-game settings, the ones for this ROM are picked when it is loaded
+    pub config: Config,
+}
+
+#[derive(Debug)]
+pub struct Cartridge {
+    file_name: String,
+    rom_size: u32,
+    rom_data: Vec<u8>,
+    // The patch applied to the ROM when it was loaded
+    patch_path: Option<String>,
+    // Settings for this ROM from the config file
+    config: GameConfig,
+    pub rom_header: RomHeader,
+}
+
+impl Cartridge {
+    /**
+     * Load a ROM file, or a ROM inside a .zip or .gz archive, and apply a patch to it
+     */
+    pub fn new(path: &str, options: &LoadOptions) -> io::Result<Self> {
+        let rom_file = archive::read_rom(path, options.archive_entry.as_deref())?;
+        let mut rom_data = rom_file.data;
+
+        let patch_path = match &options.patch {
+            Some(patch_path) => Some(patch_path.clone()),
+            None if !options.skip_patch_search => find_patch(&rom_file.path),
+            None => None,
+        };
+        if let Some(patch_path) = &patch_path {
+            let patch = fs::read(patch_path)?;
+            rom_data = patch::apply_patch(&rom_data, &patch).map_err(|error| {
+                io::Error::new(
+                    error.kind(),
+                    format!("Failed to apply {}: {}", patch_path, error),
+                )
+            })?;
+        }
+
+        let mut cartridge = Self::from_data(rom_file.path, rom_data, &options.config)?;
+        cartridge.patch_path = patch_path;
+        Ok(cartridge)
+    }
+
+    /**
+     * Make a cartridge from a ROM that is already in memory, the file name is only used to name
+     * the save
+     */
+    pub fn from_data(file_name: String, rom_data: Vec<u8>, config: &Config) -> io::Result<Self> {
+        let rom_size = rom_data.len() as u32;
+
+        let rom_header = RomHeader::parse(&rom_data)?;
+        let config = config.resolve(&rom_data, &rom_header.title);
+
+        Ok(Self {
+            file_name,
+            rom_size,
+            rom_data,
+            patch_path: None,
+            config,
+            rom_header,
+        })
+    }
+
+    /**
+     * Check the ROM against its header, see ValidationReport
+     */
+    pub fn validate(&self) -> ValidationReport {
+        ValidationReport::new(&self.rom_data, &self.rom_header)
+    }
+
+    /**
+     * The cartridge type from the header, unless the config overrides it
+     */
+    pub fn get_cartridge_type(&self) -> io::Result<CartridgeType> {
+        match self.config.mapper {
+            Some(MapperOverride::CartridgeType(cartridge_type)) => {
+                CartridgeType::try_from(cartridge_type)
+            }
+            _ => self.rom_header.get_cartridge_type(),
+        }
+    }
+
+    pub fn get_config(&self) -> &GameConfig {
+        &self.config
+    }
+
+    pub fn get_patch_path(&self) -> Option<&str> {
+        self.patch_path.as_deref()
+    }
+
+    pub fn get_data(&self) -> &Vec<u8> {
+        return &self.rom_data;
+    }
+
+    /**
+     * Path of the ROM, for ROMs in an archive this is where the ROM would be if it was extracted
+     */
+    pub fn get_file_name(&self) -> &str {
+        return &self.file_name;
+    }
+}
+
+/**
+ * Find a patch next to the ROM with the same name, like game.gb and game.ips
+ */
+fn find_patch(rom_path: &str) -> Option<String> {
+    PATCH_EXTENSIONS
+        .iter()
+        .map(|extension| Path::new(rom_path).with_extension(extension))
+        .find(|patch_path| patch_path.is_file())
+        .map(|patch_path| patch_path.to_string_lossy().to_string())
+}
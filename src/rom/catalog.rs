@@ -0,0 +1,187 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::rom::cartridge::{Cartridge, LoadOptions};
+use crate::rom::database::{GameDatabase, GameEntry, RomHashes};
+
+// Files that can hold a ROM, archives are opened to find the ROM inside
+const SCANNED_EXTENSIONS: [&str; 5] = ["gb", "gbc", "sgb", "zip", "gz"];
+
+/**
+ * One ROM in a library scan
+ */
+#[derive(Debug, Serialize)]
+pub struct CatalogEntry {
+    pub path: String,
+    // Set when the file couldn't be loaded, the other fields are empty
+    pub error: Option<String>,
+    pub title: Option<String>,
+    pub mapper: Option<String>,
+    #[serde(flatten)]
+    pub hashes: Option<RomHashes>,
+    // Passes every check in ValidationReport
+    pub is_valid: Option<bool>,
+    #[serde(rename = "match")]
+    pub game: Option<GameEntry>,
+}
+
+impl CatalogEntry {
+    fn new(path: &Path, database: &GameDatabase) -> Self {
+        let path_name = path.to_string_lossy().to_string();
+        // The scan catalogs dumps as they are, patches are ignored
+        let options = LoadOptions {
+            skip_patch_search: true,
+            ..Default::default()
+        };
+
+        match Cartridge::new(&path_name, &options) {
+            Ok(cartridge) => {
+                let hashes = RomHashes::new(cartridge.get_data());
+                CatalogEntry {
+                    path: path_name,
+                    error: None,
+                    title: Some(cartridge.rom_header.title.clone()),
+                    mapper: cartridge
+                        .rom_header
+                        .get_cartridge_type()
+                        .ok()
+                        .map(|cartridge_type| format!("{:?}", cartridge_type.mapper)),
+                    is_valid: Some(cartridge.validate().is_valid()),
+                    game: database.find(&hashes).cloned(),
+                    hashes: Some(hashes),
+                }
+            }
+            Err(error) => CatalogEntry {
+                path: path_name,
+                error: Some(error.to_string()),
+                title: None,
+                mapper: None,
+                hashes: None,
+                is_valid: None,
+                game: None,
+            },
+        }
+    }
+}
+
+/**
+ * Walk a folder and identify every ROM in it, files that fail to load are listed with the error
+ */
+pub fn scan(dir: &Path, database: &GameDatabase) -> io::Result<Vec<CatalogEntry>> {
+    let mut paths = Vec::new();
+    find_roms(dir, &mut paths)?;
+    paths.sort();
+
+    Ok(paths
+        .iter()
+        .map(|path| CatalogEntry::new(path, database))
+        .collect())
+}
+
+/**
+ * Links to folders aren't followed, a link back up the tree would loop forever
+ */
+fn find_roms(dir: &Path, paths: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+
+        if entry.file_type()?.is_dir() {
+            find_roms(&path, paths)?;
+        } else if path.is_file()
+            && path.extension().is_some_and(|extension| {
+                SCANNED_EXTENSIONS
+                    .iter()
+                    .any(|scanned| extension.eq_ignore_ascii_case(scanned))
+            })
+        {
+            paths.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+const CSV_HEADER: [&str; 12] = [
+    "path",
+    "error",
+    "title",
+    "mapper",
+    "size",
+    "crc32",
+    "sha1",
+    "is_valid",
+    "name",
+    "region",
+    "revision",
+    "is_bad_dump",
+];
+
+/**
+ * Write the scan as CSV, with one row per ROM
+ */
+pub fn to_csv(entries: &[CatalogEntry]) -> String {
+    let mut csv = CSV_HEADER.join(",");
+    csv.push('\n');
+
+    for entry in entries {
+        let hashes = entry.hashes.as_ref();
+        let game = entry.game.as_ref();
+        let row = [
+            Some(entry.path.clone()),
+            entry.error.clone(),
+            entry.title.clone(),
+            entry.mapper.clone(),
+            hashes.map(|hashes| hashes.size.to_string()),
+            hashes.map(|hashes| hashes.crc32.clone()),
+            hashes.map(|hashes| hashes.sha1.clone()),
+            entry.is_valid.map(|is_valid| is_valid.to_string()),
+            game.map(|game| game.name.clone()),
+            game.and_then(|game| game.region.clone()),
+            game.and_then(|game| game.revision.clone()),
+            game.map(|game| game.is_bad_dump.to_string()),
+        ];
+
+        let fields: Vec<String> = row
+            .into_iter()
+            .map(|field| escape_csv(&field.unwrap_or_default()))
+            .collect();
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+
+    csv
+}
+
+fn escape_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn does_not_follow_folder_links() {
+        let dir = std::env::temp_dir().join("catalog_folder_links");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("roms")).unwrap();
+        fs::write(dir.join("roms/game.gb"), [0; 0x150]).unwrap();
+        fs::write(dir.join("roms/notes.txt"), "").unwrap();
+        // A link back up the tree
+        std::os::unix::fs::symlink(&dir, dir.join("roms/loop")).unwrap();
+
+        let mut paths = Vec::new();
+        find_roms(&dir, &mut paths).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(paths, [dir.join("roms/game.gb")]);
+    }
+}
@@ -0,0 +1,232 @@
+use std::fs;
+use std::io;
+
+use serde::Serialize;
+
+// Offline copy of a No-Intro style DAT, see the file for how to fill it in
+const EMBEDDED_DAT: &str = include_str!("gameboy.dat");
+
+// Regions used in No-Intro names, a name can list several separated by commas
+const REGIONS: [&str; 20] = [
+    "World",
+    "USA",
+    "Europe",
+    "Japan",
+    "Asia",
+    "Australia",
+    "Brazil",
+    "Canada",
+    "China",
+    "France",
+    "Germany",
+    "Hong Kong",
+    "Italy",
+    "Korea",
+    "Netherlands",
+    "Spain",
+    "Sweden",
+    "Taiwan",
+    "United Kingdom",
+    "Unknown",
+];
+
+/**
+ * Hashes that identify a ROM dump
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RomHashes {
+    pub size: usize,
+    // Upper case hex, like in DATs
+    pub crc32: String,
+    pub sha1: String,
+}
+
+impl RomHashes {
+    pub fn new(rom: &[u8]) -> Self {
+        RomHashes {
+            size: rom.len(),
+            crc32: format!("{:08X}", crc32fast::hash(rom)),
+            sha1: sha1_smol::Sha1::from(rom)
+                .digest()
+                .to_string()
+                .to_uppercase(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GameEntry {
+    // Canonical No-Intro name, like "Tetris (World) (Rev 1)"
+    pub name: String,
+    pub region: Option<String>,
+    pub revision: Option<String>,
+    pub is_bad_dump: bool,
+    #[serde(skip)]
+    size: Option<usize>,
+    #[serde(skip)]
+    crc32: Option<String>,
+    #[serde(skip)]
+    sha1: Option<String>,
+}
+
+impl GameEntry {
+    fn matches(&self, hashes: &RomHashes) -> bool {
+        // SHA-1 is preferred, CRC32 collisions are rare but they happen in big sets
+        if let Some(sha1) = &self.sha1 {
+            return *sha1 == hashes.sha1;
+        }
+
+        self.crc32.as_ref() == Some(&hashes.crc32)
+            && self.size.is_none_or(|size| size == hashes.size)
+    }
+}
+
+#[derive(Debug)]
+pub struct GameDatabase {
+    entries: Vec<GameEntry>,
+}
+
+impl GameDatabase {
+    /**
+     * The DAT shipped with the emulator, used when none is set in the config or on the command line
+     */
+    pub fn embedded() -> io::Result<Self> {
+        GameDatabase::parse(EMBEDDED_DAT).map_err(|error| {
+            io::Error::new(error.kind(), format!("Invalid embedded DAT: {}", error))
+        })
+    }
+
+    /**
+     * Load a Logiqx XML DAT, like the "Nintendo - Game Boy" ones No-Intro publishes
+     */
+    pub fn load(path: &str) -> io::Result<Self> {
+        let dat = fs::read_to_string(path).map_err(|error| {
+            io::Error::new(
+                error.kind(),
+                format!("Failed to read DAT {}: {}", path, error),
+            )
+        })?;
+        GameDatabase::parse(&dat).map_err(|error| {
+            io::Error::new(error.kind(), format!("Invalid DAT {}: {}", path, error))
+        })
+    }
+
+    fn parse(dat: &str) -> io::Result<Self> {
+        // No-Intro DATs start with the Logiqx DOCTYPE
+        let options = roxmltree::ParsingOptions {
+            allow_dtd: true,
+            ..Default::default()
+        };
+        let document = roxmltree::Document::parse_with_options(dat, options)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))?;
+
+        let mut entries = Vec::new();
+        for game in document
+            .descendants()
+            .filter(|node| node.has_tag_name("game") || node.has_tag_name("machine"))
+        {
+            let name = game.attribute("name").unwrap_or_default().to_string();
+
+            for rom in game.children().filter(|node| node.has_tag_name("rom")) {
+                entries.push(GameEntry {
+                    region: parse_region(&name),
+                    revision: parse_revision(&name),
+                    is_bad_dump: rom.attribute("status") == Some("baddump") || name.contains("[b]"),
+                    size: rom.attribute("size").and_then(|size| size.parse().ok()),
+                    crc32: rom.attribute("crc").map(str::to_uppercase),
+                    sha1: rom.attribute("sha1").map(str::to_uppercase),
+                    name: name.clone(),
+                });
+            }
+        }
+
+        Ok(GameDatabase { entries })
+    }
+
+    pub fn find(&self, hashes: &RomHashes) -> Option<&GameEntry> {
+        self.entries.iter().find(|entry| entry.matches(hashes))
+    }
+}
+
+/**
+ * The region is the first group in parentheses, like "(USA, Europe)"
+ */
+fn parse_region(name: &str) -> Option<String> {
+    get_tags(name)
+        .find(|tag| tag.split(", ").all(|region| REGIONS.contains(&region)))
+        .map(str::to_string)
+}
+
+/**
+ * Revisions are tagged "(Rev 1)", "(Rev A)" or with a version like "(v1.1)"
+ */
+fn parse_revision(name: &str) -> Option<String> {
+    get_tags(name)
+        .find(|tag| {
+            tag.starts_with("Rev ")
+                || (tag.starts_with('v') && tag[1..].starts_with(|c: char| c.is_ascii_digit()))
+        })
+        .map(str::to_string)
+}
+
+fn get_tags(name: &str) -> impl Iterator<Item = &str> {
+    name.split('(')
+        .skip(1)
+        .filter_map(|tag| tag.split_once(')').map(|(tag, _)| tag))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAT: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE datafile PUBLIC "-//Logiqx//DTD ROM Management Datafile//EN" "http://www.logiqx.com/Dats/datafile.dtd">
+<datafile>
+	<header>
+		<name>Nintendo - Game Boy</name>
+	</header>
+	<game name="Test Game (USA, Europe) (Rev 1)">
+		<description>Test Game (USA, Europe) (Rev 1)</description>
+		<rom name="Test Game (USA, Europe) (Rev 1).gb" size="4" crc="b63cfbcd" sha1="12dada1fff4d4787ade3333147202c3b443e376f"/>
+	</game>
+	<game name="Other Game (Japan) (v1.1)">
+		<rom name="Other Game (Japan) (v1.1).gb" size="4" crc="12345678" status="baddump"/>
+	</game>
+</datafile>
+"#;
+
+    #[test]
+    fn parses_dat() {
+        let database = GameDatabase::parse(DAT).unwrap();
+        assert_eq!(database.entries.len(), 2);
+
+        let game = &database.entries[0];
+        assert_eq!(game.name, "Test Game (USA, Europe) (Rev 1)");
+        assert_eq!(game.region.as_deref(), Some("USA, Europe"));
+        assert_eq!(game.revision.as_deref(), Some("Rev 1"));
+        assert!(!game.is_bad_dump);
+
+        let game = &database.entries[1];
+        assert_eq!(game.region.as_deref(), Some("Japan"));
+        assert_eq!(game.revision.as_deref(), Some("v1.1"));
+        assert!(game.is_bad_dump);
+    }
+
+    #[test]
+    fn finds_games_by_hash() {
+        let database = GameDatabase::parse(DAT).unwrap();
+        let game = database.find(&RomHashes::new(&[1, 2, 3, 4])).unwrap();
+        assert_eq!(game.name, "Test Game (USA, Europe) (Rev 1)");
+        assert!(database.find(&RomHashes::new(&[5, 6, 7, 8])).is_none());
+    }
+
+    #[test]
+    fn embedded_dat_is_valid() {
+        assert!(GameDatabase::embedded().is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_dat() {
+        assert!(GameDatabase::parse("<datafile><game></datafile>").is_err());
+    }
+}
@@ -0,0 +1,191 @@
+use std::io;
+
+use crate::rom::rom_header::{
+    CARTRIDGE_TYPE_ADDR, CGB_FLAG_ADDR, CartridgeType, CgbFlag, GLOBAL_CHECKSUM_ADDR,
+    HEADER_CHECKSUM_ADDR, LOGO_START, NEW_LICENSEE_CODE_START, NINTENDO_LOGO,
+    OLD_LICENSEE_CODE_ADDR, OldLicenseCode, RAM_SIZE_ADDR, ROM_SIZE_ADDR, RamSize, RomSize,
+    SGB_FLAG, SGB_FLAG_ADDR, TITLE_START,
+};
+use crate::rom::validation::{compute_global_checksum, compute_header_checksum};
+
+// Unused ROM space is filled with the value unprogrammed flash reads as
+const PADDING: u8 = 0xFF;
+const MAX_TITLE_LENGTH: usize = CGB_FLAG_ADDR + 1 - TITLE_START;
+
+/**
+ * Header fields to overwrite, fields that are None are left as they are. The logo, the ROM size
+ * and the checksums are always fixed.
+ */
+#[derive(Debug, Default)]
+pub struct HeaderFix {
+    pub title: Option<String>,
+    pub cgb_flag: Option<CgbFlag>,
+    pub is_sgb: bool,
+    // 2 character new licensee code, the old licensee code is set to 0x33 to use it
+    pub new_licensee_code: Option<String>,
+    pub old_licensee_code: Option<u8>,
+    pub cartridge_type: Option<u8>,
+    pub ram_size: Option<u8>,
+}
+
+/**
+ * Rewrite a ROM's header so it passes every check in ValidationReport, like rgbfix
+ */
+pub fn fix_rom(rom: &mut Vec<u8>, fix: &HeaderFix) -> io::Result<()> {
+    let rom_size = RomSize::fit(rom.len()).ok_or_else(|| {
+        invalid_input(format!(
+            "A {} byte ROM is too big for any cartridge",
+            rom.len()
+        ))
+    })?;
+    rom.resize(rom_size.get_size(), PADDING);
+    rom[ROM_SIZE_ADDR] = rom_size as u8;
+
+    rom[LOGO_START..LOGO_START + NINTENDO_LOGO.len()].copy_from_slice(&NINTENDO_LOGO);
+
+    if let Some(cgb_flag) = fix.cgb_flag {
+        rom[CGB_FLAG_ADDR] = match cgb_flag {
+            CgbFlag::DmgOnly => 0x00,
+            CgbFlag::Compatible => 0x80,
+            CgbFlag::CgbOnly => 0xC0,
+        };
+    }
+
+    if let Some(title) = &fix.title {
+        write_title(rom, title)?;
+    }
+
+    if let Some(code) = &fix.new_licensee_code {
+        if code.len() != 2 || !code.is_ascii() {
+            return Err(invalid_input(format!(
+                "The licensee code {} must be 2 ASCII characters",
+                code
+            )));
+        }
+        rom[NEW_LICENSEE_CODE_START..NEW_LICENSEE_CODE_START + 2].copy_from_slice(code.as_bytes());
+        rom[OLD_LICENSEE_CODE_ADDR] = OldLicenseCode::NewLicenseCode as u8;
+    }
+
+    if let Some(code) = fix.old_licensee_code {
+        rom[OLD_LICENSEE_CODE_ADDR] = code;
+    }
+
+    if fix.is_sgb {
+        rom[SGB_FLAG_ADDR] = SGB_FLAG;
+        if rom[OLD_LICENSEE_CODE_ADDR] != OldLicenseCode::NewLicenseCode as u8 {
+            return Err(invalid_input(
+                "The SGB flag needs the old licensee code to be 0x33".to_string(),
+            ));
+        }
+    }
+
+    if let Some(cartridge_type) = fix.cartridge_type {
+        CartridgeType::try_from(cartridge_type)?;
+        rom[CARTRIDGE_TYPE_ADDR] = cartridge_type;
+    }
+
+    if let Some(ram_size) = fix.ram_size {
+        RamSize::try_from(ram_size)
+            .map_err(|_| invalid_input(format!("Unknown RAM size {:#04X}", ram_size)))?;
+        rom[RAM_SIZE_ADDR] = ram_size;
+    }
+
+    // The global checksum covers the header checksum, so it has to go last
+    rom[HEADER_CHECKSUM_ADDR] = compute_header_checksum(rom);
+    let global_checksum = compute_global_checksum(rom);
+    rom[GLOBAL_CHECKSUM_ADDR..GLOBAL_CHECKSUM_ADDR + 2]
+        .copy_from_slice(&global_checksum.to_be_bytes());
+
+    Ok(())
+}
+
+/**
+ * The title is padded with zeros, it loses its last byte to the CGB flag on CGB cartridges
+ */
+fn write_title(rom: &mut [u8], title: &str) -> io::Result<()> {
+    let max_length = match CgbFlag::from(rom[CGB_FLAG_ADDR]) {
+        CgbFlag::DmgOnly => MAX_TITLE_LENGTH,
+        _ => MAX_TITLE_LENGTH - 1,
+    };
+
+    if !title.is_ascii() || title.len() > max_length {
+        return Err(invalid_input(format!(
+            "The title {} must be at most {} ASCII characters",
+            title, max_length
+        )));
+    }
+
+    let title_area = &mut rom[TITLE_START..TITLE_START + max_length];
+    title_area.fill(0);
+    title_area[..title.len()].copy_from_slice(title.as_bytes());
+    Ok(())
+}
+
+fn invalid_input(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rom::rom_header::RomHeader;
+    use crate::rom::validation::ValidationReport;
+
+    fn validate(rom: &Vec<u8>) -> ValidationReport {
+        ValidationReport::new(rom, &RomHeader::parse(rom).unwrap())
+    }
+
+    #[test]
+    fn fixed_rom_is_valid() {
+        let mut rom = vec![0; 0x5000];
+        fix_rom(&mut rom, &HeaderFix::default()).unwrap();
+        assert_eq!(rom.len(), 0x8000);
+        assert_eq!(rom[0x7FFF], PADDING);
+        assert!(validate(&rom).is_valid());
+    }
+
+    #[test]
+    fn writes_requested_fields() {
+        let mut rom = vec![0; 0x8000];
+        let fix = HeaderFix {
+            title: Some("TEST".to_string()),
+            cgb_flag: Some(CgbFlag::Compatible),
+            is_sgb: true,
+            new_licensee_code: Some("01".to_string()),
+            cartridge_type: Some(0x1B),
+            ram_size: Some(0x03),
+            ..Default::default()
+        };
+        fix_rom(&mut rom, &fix).unwrap();
+
+        let header = RomHeader::parse(&rom).unwrap();
+        assert_eq!(header.title, "TEST");
+        assert_eq!(header.cgb_flag, CgbFlag::Compatible);
+        assert!(header.sgb_flag);
+        assert_eq!(header.cartridge_type, 0x1B);
+        assert_eq!(header.ram_size, 0x03);
+        assert!(validate(&rom).is_valid());
+    }
+
+    #[test]
+    fn rejects_bad_fields() {
+        let too_long = HeaderFix {
+            title: Some("A TITLE TOO LONG".to_string()),
+            cgb_flag: Some(CgbFlag::CgbOnly),
+            ..Default::default()
+        };
+        let sgb_without_licensee = HeaderFix {
+            is_sgb: true,
+            ..Default::default()
+        };
+        let unknown_mapper = HeaderFix {
+            cartridge_type: Some(0x04),
+            ..Default::default()
+        };
+
+        for fix in [too_long, sgb_without_licensee, unknown_mapper] {
+            assert!(fix_rom(&mut vec![0; 0x8000], &fix).is_err());
+        }
+        assert!(fix_rom(&mut vec![0; 0x800001], &HeaderFix::default()).is_err());
+    }
+}
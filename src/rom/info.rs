@@ -0,0 +1,147 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::rom::cartridge::Cartridge;
+
+/**
+ * Everything known about a ROM from its header, flattened so it can be printed or dumped as JSON
+ */
+#[derive(Debug, Serialize)]
+pub struct RomInfo {
+    pub title: String,
+    pub patch: Option<String>,
+    pub manufacturer_code: Option<String>,
+    pub licensee: String,
+    pub destination: String,
+    pub version: u8,
+    pub cgb_flag: String,
+    pub sgb_flag: bool,
+    pub is_sgb_supported: bool,
+    pub cartridge_type: u8,
+    // None when the cartridge type is unknown
+    pub mapper: Option<String>,
+    pub features: Vec<&'static str>,
+    pub rom_size_code: u8,
+    pub rom_size: Option<usize>,
+    pub rom_banks: Option<usize>,
+    pub ram_size_code: u8,
+    pub ram_size: Option<usize>,
+    pub file_size: usize,
+    pub is_logo_valid: bool,
+    pub header_checksum: u8,
+    pub computed_header_checksum: u8,
+    pub global_checksum: u16,
+    pub computed_global_checksum: u16,
+}
+
+impl RomInfo {
+    pub fn new(cartridge: &Cartridge) -> Self {
+        let header = &cartridge.rom_header;
+        let report = cartridge.validate();
+        let cartridge_type = header.get_cartridge_type().ok();
+        let rom_size = header.get_rom_size().ok();
+
+        let features = cartridge_type.map_or(Vec::new(), |cartridge_type| {
+            [
+                (cartridge_type.has_ram, "RAM"),
+                (cartridge_type.has_battery, "BATTERY"),
+                (cartridge_type.has_timer, "TIMER"),
+                (cartridge_type.has_rumble, "RUMBLE"),
+                (cartridge_type.has_sensor, "SENSOR"),
+            ]
+            .into_iter()
+            .filter_map(|(has_feature, name)| has_feature.then_some(name))
+            .collect()
+        });
+
+        RomInfo {
+            title: header.title.clone(),
+            patch: cartridge.get_patch_path().map(str::to_string),
+            manufacturer_code: header.manufacturer_code.clone(),
+            licensee: header.get_licensee(),
+            destination: format!("{:?}", header.destination_code),
+            version: header.mask_rom_version_number,
+            cgb_flag: format!("{:?}", report.cgb_flag),
+            sgb_flag: report.has_sgb_flag,
+            is_sgb_supported: report.is_sgb_supported,
+            cartridge_type: header.cartridge_type,
+            mapper: cartridge_type.map(|cartridge_type| format!("{:?}", cartridge_type.mapper)),
+            features,
+            rom_size_code: header.rom_size,
+            rom_size: rom_size.map(|size| size.get_size()),
+            rom_banks: rom_size.map(|size| size.get_bank_count()),
+            ram_size_code: header.ram_size,
+            ram_size: header.get_ram_size().ok().map(|size| size.get_size()),
+            file_size: report.actual_size,
+            is_logo_valid: report.is_logo_valid,
+            header_checksum: report.header_checksum.expected,
+            computed_header_checksum: report.header_checksum.actual,
+            global_checksum: report.global_checksum.expected,
+            computed_global_checksum: report.global_checksum.actual,
+        }
+    }
+}
+
+impl fmt::Display for RomInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let or_unknown = |value: Option<String>| value.unwrap_or_else(|| "unknown".to_string());
+
+        writeln!(f, "Title:             {}", self.title)?;
+        if let Some(patch) = &self.patch {
+            writeln!(f, "Patch:             {}", patch)?;
+        }
+        writeln!(
+            f,
+            "Manufacturer code: {}",
+            self.manufacturer_code.as_deref().unwrap_or("none")
+        )?;
+        writeln!(f, "Licensee:          {}", self.licensee)?;
+        writeln!(f, "Destination:       {}", self.destination)?;
+        writeln!(f, "Version:           {}", self.version)?;
+        writeln!(f, "CGB flag:          {}", self.cgb_flag)?;
+        writeln!(
+            f,
+            "SGB flag:          {}{}",
+            self.sgb_flag,
+            if self.sgb_flag && !self.is_sgb_supported {
+                " (ignored, old licensee code is not 0x33)"
+            } else {
+                ""
+            }
+        )?;
+        let mut cartridge_type = vec![or_unknown(self.mapper.clone())];
+        cartridge_type.extend(self.features.iter().map(|feature| feature.to_string()));
+        writeln!(
+            f,
+            "Cartridge type:    {:#04X} {}",
+            self.cartridge_type,
+            cartridge_type.join("+")
+        )?;
+        writeln!(
+            f,
+            "ROM size:          {:#04X} {} bytes, {} banks",
+            self.rom_size_code,
+            or_unknown(self.rom_size.map(|size| size.to_string())),
+            or_unknown(self.rom_banks.map(|banks| banks.to_string()))
+        )?;
+        writeln!(
+            f,
+            "RAM size:          {:#04X} {} bytes",
+            self.ram_size_code,
+            or_unknown(self.ram_size.map(|size| size.to_string()))
+        )?;
+        writeln!(f, "File size:         {} bytes", self.file_size)?;
+        writeln!(f, "Nintendo logo:     {}", self.is_logo_valid)?;
+        writeln!(
+            f,
+            "Header checksum:   {:#04X} (computed {:#04X})",
+            self.header_checksum, self.computed_header_checksum
+        )?;
+        write!(
+            f,
+            "Global checksum:   {:#06X} (computed {:#06X})",
+            self.global_checksum, self.computed_global_checksum
+        )
+    }
+}
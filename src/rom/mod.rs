@@ -1,2 +1,11 @@
+pub mod archive;
+pub mod boot_rom;
 pub mod cartridge;
-mod rom_header;
+pub mod catalog;
+pub mod database;
+pub mod fixer;
+pub mod info;
+pub mod patch;
+pub(crate) mod rom_header;
+pub mod save;
+pub mod validation;
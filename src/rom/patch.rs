@@ -0,0 +1,502 @@
+use std::io;
+
+const IPS_MAGIC: &[u8] = b"PATCH";
+const IPS_EOF: &[u8] = b"EOF";
+const UPS_MAGIC: &[u8] = b"UPS1";
+const BPS_MAGIC: &[u8] = b"BPS1";
+
+// UPS and BPS end with the CRC32 of the source, the target and the patch itself
+const FOOTER_SIZE: usize = 12;
+
+// The biggest cartridges are 8 MiB, the rest leaves room for overdumps. Patches claiming a bigger
+// ROM are corrupt, and would otherwise allocate whatever size they ask for.
+const MAX_TARGET_SIZE: usize = 0x1000000;
+
+// BPS actions, the low 2 bits of each action
+const SOURCE_READ: u64 = 0;
+const TARGET_READ: u64 = 1;
+const SOURCE_COPY: u64 = 2;
+const TARGET_COPY: u64 = 3;
+
+pub(crate) const PATCH_EXTENSIONS: [&str; 3] = ["ips", "ups", "bps"];
+
+/**
+ * Apply an IPS, UPS or BPS patch to a ROM, the format is detected from the patch header
+ */
+pub fn apply_patch(rom: &[u8], patch: &[u8]) -> io::Result<Vec<u8>> {
+    if patch.starts_with(IPS_MAGIC) {
+        apply_ips(rom, patch)
+    } else if patch.starts_with(UPS_MAGIC) {
+        apply_ups(rom, patch)
+    } else if patch.starts_with(BPS_MAGIC) {
+        apply_bps(rom, patch)
+    } else {
+        Err(invalid_patch("Unknown patch format"))
+    }
+}
+
+/**
+ * IPS is a list of records that overwrite the ROM, with no checksums
+ */
+fn apply_ips(rom: &[u8], patch: &[u8]) -> io::Result<Vec<u8>> {
+    let mut target = rom.to_vec();
+    let mut reader = PatchReader::new(patch, IPS_MAGIC.len());
+
+    loop {
+        if reader.remaining().starts_with(IPS_EOF) {
+            reader.read_bytes(IPS_EOF.len())?;
+            break;
+        }
+
+        let offset = reader.read_be(3)?;
+        let length = reader.read_be(2)?;
+        // A zero length record is run-length encoded
+        let data = if length == 0 {
+            let length = reader.read_be(2)?;
+            let value = reader.read_bytes(1)?[0];
+            vec![value; length]
+        } else {
+            reader.read_bytes(length)?.to_vec()
+        };
+
+        if target.len() < offset + data.len() {
+            target.resize(offset + data.len(), 0);
+        }
+        target[offset..offset + data.len()].copy_from_slice(&data);
+    }
+
+    // Some patches truncate the ROM after the end marker
+    if reader.remaining().len() == 3 {
+        let length = reader.read_be(3)?;
+        target.truncate(length);
+    }
+
+    Ok(target)
+}
+
+/**
+ * UPS XORs runs of bytes into the ROM
+ */
+fn apply_ups(rom: &[u8], patch: &[u8]) -> io::Result<Vec<u8>> {
+    let checksums = read_checksums(patch)?;
+    checksums.check_source(rom)?;
+
+    let mut reader = PatchReader::new(&patch[..patch.len() - FOOTER_SIZE], UPS_MAGIC.len());
+    let source_size = reader.read_number()? as usize;
+    let target_size = reader.read_number()? as usize;
+    if source_size != rom.len() {
+        return Err(invalid_patch(&format!(
+            "The patch is for a {} byte ROM, but the ROM is {} bytes",
+            source_size,
+            rom.len()
+        )));
+    }
+    check_target_size(target_size)?;
+
+    let mut target = rom.to_vec();
+    target.resize(target_size, 0);
+
+    let mut offset: usize = 0;
+    while !reader.remaining().is_empty() {
+        // Runs can start at the end of the target, as long as they are empty
+        offset = usize::try_from(reader.read_number()?)
+            .ok()
+            .and_then(|skip| offset.checked_add(skip))
+            .filter(|offset| *offset <= target.len())
+            .ok_or_else(|| invalid_patch("The patch skips past the end of the ROM"))?;
+        loop {
+            let value = reader.read_bytes(1)?[0];
+            // The run ends with a 0, which also skips a byte
+            if value == 0 {
+                offset += 1;
+                break;
+            }
+            let byte = target
+                .get_mut(offset)
+                .ok_or_else(|| invalid_patch("The patch writes past the end of the ROM"))?;
+            *byte ^= value;
+            offset += 1;
+        }
+    }
+
+    checksums.check_target(&target)?;
+    Ok(target)
+}
+
+/**
+ * BPS builds the new ROM from copies of the old ROM, the patch and itself
+ */
+fn apply_bps(rom: &[u8], patch: &[u8]) -> io::Result<Vec<u8>> {
+    let checksums = read_checksums(patch)?;
+    checksums.check_source(rom)?;
+
+    let mut reader = PatchReader::new(&patch[..patch.len() - FOOTER_SIZE], BPS_MAGIC.len());
+    let source_size = reader.read_number()? as usize;
+    let target_size = reader.read_number()? as usize;
+    let metadata_size = reader.read_number()? as usize;
+    reader.read_bytes(metadata_size)?;
+    if source_size != rom.len() {
+        return Err(invalid_patch(&format!(
+            "The patch is for a {} byte ROM, but the ROM is {} bytes",
+            source_size,
+            rom.len()
+        )));
+    }
+    check_target_size(target_size)?;
+
+    let mut target = Vec::with_capacity(target_size);
+    let mut source_offset: usize = 0;
+    let mut target_offset: usize = 0;
+
+    while !reader.remaining().is_empty() {
+        let action = reader.read_number()?;
+        let length = (action >> 2) as usize + 1;
+        if length > target_size - target.len() {
+            return Err(invalid_patch("The patch writes past the end of the ROM"));
+        }
+
+        match action & 0x03 {
+            SOURCE_READ => {
+                let start = target.len();
+                let data = rom
+                    .get(start..start + length)
+                    .ok_or_else(|| invalid_patch("Source read past the end of the ROM"))?;
+                target.extend_from_slice(data);
+            }
+            TARGET_READ => target.extend_from_slice(reader.read_bytes(length)?),
+            SOURCE_COPY => {
+                source_offset = read_relative_offset(&mut reader, source_offset)?;
+                let data = rom
+                    .get(source_offset..source_offset.saturating_add(length))
+                    .ok_or_else(|| invalid_patch("Source copy past the end of the ROM"))?;
+                target.extend_from_slice(data);
+                source_offset += length;
+            }
+            TARGET_COPY => {
+                target_offset = read_relative_offset(&mut reader, target_offset)?;
+                // The copy can overlap what it is writing, so it goes a byte at a time
+                for _ in 0..length {
+                    let byte = *target
+                        .get(target_offset)
+                        .ok_or_else(|| invalid_patch("Target copy past the end of the ROM"))?;
+                    target.push(byte);
+                    target_offset += 1;
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    if target.len() != target_size {
+        return Err(invalid_patch(&format!(
+            "The patch made a {} byte ROM, but it should be {} bytes",
+            target.len(),
+            target_size
+        )));
+    }
+
+    checksums.check_target(&target)?;
+    Ok(target)
+}
+
+fn check_target_size(target_size: usize) -> io::Result<()> {
+    if target_size > MAX_TARGET_SIZE {
+        return Err(invalid_patch(&format!(
+            "The patch makes a {} byte ROM, but ROMs are at most {} bytes",
+            target_size, MAX_TARGET_SIZE
+        )));
+    }
+    Ok(())
+}
+
+fn read_relative_offset(reader: &mut PatchReader, offset: usize) -> io::Result<usize> {
+    let data = reader.read_number()?;
+    let distance = (data >> 1) as usize;
+
+    let offset = if data & 0x01 != 0 {
+        offset.checked_sub(distance)
+    } else {
+        offset.checked_add(distance)
+    };
+    offset.ok_or_else(|| invalid_patch("Copy offset out of range"))
+}
+
+struct Checksums {
+    source: u32,
+    target: u32,
+}
+
+impl Checksums {
+    fn check_source(&self, rom: &[u8]) -> io::Result<()> {
+        let crc = crc32fast::hash(rom);
+        if crc != self.source {
+            return Err(invalid_patch(&format!(
+                "The patch is for a ROM with CRC32 {:08X}, but the ROM's CRC32 is {:08X}. \
+                 Check that it is the right game and revision.",
+                self.source, crc
+            )));
+        }
+        Ok(())
+    }
+
+    fn check_target(&self, rom: &[u8]) -> io::Result<()> {
+        let crc = crc32fast::hash(rom);
+        if crc != self.target {
+            return Err(invalid_patch(&format!(
+                "The patched ROM should have CRC32 {:08X}, but it has CRC32 {:08X}",
+                self.target, crc
+            )));
+        }
+        Ok(())
+    }
+}
+
+/**
+ * Read the UPS/BPS footer, after checking the patch isn't corrupt
+ */
+fn read_checksums(patch: &[u8]) -> io::Result<Checksums> {
+    if patch.len() < FOOTER_SIZE + 4 {
+        return Err(invalid_patch("The patch is truncated"));
+    }
+
+    let footer = &patch[patch.len() - FOOTER_SIZE..];
+    let read_crc = |index: usize| u32::from_le_bytes(footer[index..index + 4].try_into().unwrap());
+
+    let patch_crc = crc32fast::hash(&patch[..patch.len() - 4]);
+    if patch_crc != read_crc(8) {
+        return Err(invalid_patch(&format!(
+            "The patch should have CRC32 {:08X}, but it has CRC32 {:08X}. The patch is corrupt.",
+            read_crc(8),
+            patch_crc
+        )));
+    }
+
+    Ok(Checksums {
+        source: read_crc(0),
+        target: read_crc(4),
+    })
+}
+
+struct PatchReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> PatchReader<'a> {
+    fn new(data: &'a [u8], offset: usize) -> Self {
+        PatchReader { data, offset }
+    }
+
+    fn remaining(&self) -> &'a [u8] {
+        &self.data[self.offset.min(self.data.len())..]
+    }
+
+    fn read_bytes(&mut self, length: usize) -> io::Result<&'a [u8]> {
+        let bytes = self
+            .data
+            .get(self.offset..self.offset + length)
+            .ok_or_else(|| invalid_patch("The patch is truncated"))?;
+        self.offset += length;
+        Ok(bytes)
+    }
+
+    fn read_be(&mut self, length: usize) -> io::Result<usize> {
+        let bytes = self.read_bytes(length)?;
+        Ok(bytes
+            .iter()
+            .fold(0, |value, byte| (value << 8) | *byte as usize))
+    }
+
+    /**
+     * UPS and BPS numbers are 7 bits per byte, with the top bit set on the last byte
+     */
+    fn read_number(&mut self) -> io::Result<u64> {
+        let mut value: u64 = 0;
+        let mut shift: u64 = 1;
+
+        loop {
+            let byte = self.read_bytes(1)?[0];
+            value = value
+                .checked_add((byte & 0x7F) as u64 * shift)
+                .ok_or_else(|| invalid_patch("Number out of range"))?;
+            if byte & 0x80 != 0 {
+                return Ok(value);
+            }
+            shift = shift
+                .checked_shl(7)
+                .filter(|shift| *shift != 0)
+                .ok_or_else(|| invalid_patch("Number out of range"))?;
+            value = value
+                .checked_add(shift)
+                .ok_or_else(|| invalid_patch("Number out of range"))?;
+        }
+    }
+}
+
+fn invalid_patch(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The inverse of PatchReader::read_number
+    fn encode_number(mut value: u64) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        loop {
+            let byte = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                bytes.push(byte | 0x80);
+                return bytes;
+            }
+            bytes.push(byte);
+            value -= 1;
+        }
+    }
+
+    fn add_footer(mut patch: Vec<u8>, source: &[u8], target: &[u8]) -> Vec<u8> {
+        patch.extend(crc32fast::hash(source).to_le_bytes());
+        patch.extend(crc32fast::hash(target).to_le_bytes());
+        patch.extend(crc32fast::hash(&patch).to_le_bytes());
+        patch
+    }
+
+    fn ups_patch(source: &[u8], target: &[u8], target_size: u64) -> Vec<u8> {
+        let mut patch = UPS_MAGIC.to_vec();
+        patch.extend(encode_number(source.len() as u64));
+        patch.extend(encode_number(target_size));
+        // One run XORing the first byte
+        patch.extend(encode_number(0));
+        patch.extend([source[0] ^ target[0], 0]);
+        add_footer(patch, source, target)
+    }
+
+    fn bps_patch(source: &[u8], target: &[u8], target_size: u64, actions: &[u64]) -> Vec<u8> {
+        let mut patch = BPS_MAGIC.to_vec();
+        patch.extend(encode_number(source.len() as u64));
+        patch.extend(encode_number(target_size));
+        patch.extend(encode_number(0));
+        for action in actions {
+            patch.extend(encode_number(*action));
+        }
+        add_footer(patch, source, target)
+    }
+
+    #[test]
+    fn number_encoding_round_trips() {
+        for value in [0, 1, 0x7F, 0x80, 0x4000, 0x123456] {
+            let bytes = encode_number(value);
+            assert_eq!(PatchReader::new(&bytes, 0).read_number().unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn applies_ips_records() {
+        let rom = [0u8; 8];
+        let mut patch = IPS_MAGIC.to_vec();
+        // Copy 2 bytes to 0x000001
+        patch.extend([0, 0, 1, 0, 2, 0xAA, 0xBB]);
+        // Fill 3 bytes at 0x000006 with 0xCC, past the end of the ROM
+        patch.extend([0, 0, 6, 0, 0, 0, 3, 0xCC]);
+        patch.extend(IPS_EOF);
+
+        let target = apply_patch(&rom, &patch).unwrap();
+        assert_eq!(target, [0, 0xAA, 0xBB, 0, 0, 0, 0xCC, 0xCC, 0xCC]);
+    }
+
+    #[test]
+    fn truncates_after_ips_end_marker() {
+        let mut patch = IPS_MAGIC.to_vec();
+        patch.extend(IPS_EOF);
+        patch.extend([0, 0, 4]);
+        assert_eq!(apply_patch(&[1; 8], &patch).unwrap(), [1; 4]);
+    }
+
+    #[test]
+    fn applies_ups() {
+        let source = [1, 2, 3, 4];
+        let target = [9, 2, 3, 4];
+        let patch = ups_patch(&source, &target, 4);
+        assert_eq!(apply_patch(&source, &patch).unwrap(), target);
+    }
+
+    #[test]
+    fn rejects_wrong_source() {
+        let patch = ups_patch(&[1, 2, 3, 4], &[9, 2, 3, 4], 4);
+        assert!(apply_patch(&[5, 6, 7, 8], &patch).is_err());
+    }
+
+    #[test]
+    fn rejects_corrupt_patch() {
+        let source = [1, 2, 3, 4];
+        let mut patch = ups_patch(&source, &[9, 2, 3, 4], 4);
+        patch[UPS_MAGIC.len()] ^= 0x01;
+        assert!(apply_patch(&source, &patch).is_err());
+    }
+
+    #[test]
+    fn rejects_huge_ups_target() {
+        let source = [1, 2, 3, 4];
+        let patch = ups_patch(&source, &[9, 2, 3, 4], 1 << 40);
+        let error = apply_patch(&source, &patch).unwrap_err();
+        assert!(error.to_string().contains("at most"));
+    }
+
+    #[test]
+    fn applies_bps() {
+        let source = [1, 2, 3, 4];
+        let target = [1, 2, 1, 2, 1, 2];
+        // Read 2 bytes from the source, then copy them forward twice
+        let patch = bps_patch(
+            &source,
+            &target,
+            6,
+            &[(1 << 2) | SOURCE_READ, (3 << 2) | TARGET_COPY, 0],
+        );
+        assert_eq!(apply_patch(&source, &patch).unwrap(), target);
+    }
+
+    #[test]
+    fn rejects_huge_bps_target() {
+        let source = [1, 2, 3, 4];
+        let patch = bps_patch(&source, &source, 1 << 40, &[]);
+        let error = apply_patch(&source, &patch).unwrap_err();
+        assert!(error.to_string().contains("at most"));
+    }
+
+    #[test]
+    fn rejects_bps_action_past_target_size() {
+        let source = [1, 2, 3, 4];
+        // A 4 byte target, but the action reads 1 << 40 bytes
+        let patch = bps_patch(&source, &source, 4, &[(1 << 42) | SOURCE_READ]);
+        let error = apply_patch(&source, &patch).unwrap_err();
+        assert!(error.to_string().contains("past the end"));
+    }
+
+    #[test]
+    fn rejects_ups_offsets_past_the_target() {
+        let source = [1, 2, 3, 4];
+        let mut patch = UPS_MAGIC.to_vec();
+        patch.extend(encode_number(4));
+        patch.extend(encode_number(4));
+        // An empty run, then a skip that would overflow the offset
+        patch.extend(encode_number(0));
+        patch.push(0);
+        patch.extend(encode_number(u64::MAX - 0x80));
+        patch.push(0);
+        let error = apply_patch(&source, &add_footer(patch, &source, &source)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("skips past the end"));
+
+        // A run that goes past the end of the target
+        let mut patch = UPS_MAGIC.to_vec();
+        patch.extend(encode_number(4));
+        patch.extend(encode_number(4));
+        patch.extend(encode_number(3));
+        patch.extend([1, 1, 0]);
+        let error = apply_patch(&source, &add_footer(patch, &source, &source)).unwrap_err();
+        assert!(error.to_string().contains("writes past the end"));
+    }
+}
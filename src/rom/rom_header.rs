@@ -1,35 +1,277 @@
 use num_enum;
+use serde::Serialize;
 use std::collections::HashMap;
 
+use crate::mappers::mapper::ROM_BANK_SIZE;
+
+pub(crate) const LOGO_START: usize = 0x0104;
+pub(crate) const TITLE_START: usize = 0x0134;
+pub(crate) const MANUFACTURER_CODE_START: usize = 0x013F;
+pub(crate) const CGB_FLAG_ADDR: usize = 0x0143;
+pub(crate) const NEW_LICENSEE_CODE_START: usize = 0x0144;
+pub(crate) const SGB_FLAG_ADDR: usize = 0x0146;
+pub(crate) const CARTRIDGE_TYPE_ADDR: usize = 0x0147;
+pub(crate) const ROM_SIZE_ADDR: usize = 0x0148;
+pub(crate) const RAM_SIZE_ADDR: usize = 0x0149;
+pub(crate) const OLD_LICENSEE_CODE_ADDR: usize = 0x014B;
+// The header checksum covers the title up to the mask ROM version number
+pub(crate) const HEADER_CHECKSUM_START: usize = TITLE_START;
+pub(crate) const HEADER_CHECKSUM_ADDR: usize = 0x014D;
+pub(crate) const GLOBAL_CHECKSUM_ADDR: usize = 0x014E;
+pub(crate) const HEADER_END: usize = 0x0150;
+
+// The SGB flag is only used when the old licensee code is set to this value
+pub(crate) const SGB_FLAG: u8 = 0x03;
+
+// Bitmap of the Nintendo logo the boot ROM compares against 0x0104 - 0x0133
+pub(crate) const NINTENDO_LOGO: [u8; 48] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+];
+
 // From 0x0100 - 0x014F
 #[derive(Debug)]
 pub struct RomHeader {
-    logo: [u8; 48],                    // 0x0104 - 0x0133
-    title: String,                     // 0x0134 - 0x0143
-    cgb_flag: bool,                    // 0x0143 - ignored by us
-    manufacturer_code: Option<u64>, // 0x013F - 0x0143  was part of the title, in new Cartridges contain a 4 character code in ascii
-    new_licensee_code: Option<String>, // 0x0144 - 0x0145 2 character ASCII
-    sgb_flag: bool,                 // 0x0146
-    cartridge_type: u8,             // 0x0147
-    rom_size: u8,                   // 0x0148 32 KiB × (1 << <value>)
-    ram_size: u8,                   // 0x0149
-    destination_code: DestinationCode, //0x014A
-    old_license_code: OldLicenseCode, // 0x14B
-    mask_rom_version_number: u8,    // 0x014C
-    pub(crate) header_checksum: u8, // 0x014D
-    global_checksum: u16,           // 0x014E - 0x014F
+    pub(crate) logo: [u8; 48],                    // 0x0104 - 0x0133
+    pub(crate) title: String,                     // 0x0134 - 0x0143, shorter on CGB cartridges
+    pub(crate) cgb_flag: CgbFlag,                 // 0x0143
+    pub(crate) manufacturer_code: Option<String>, // 0x013F - 0x0142 was part of the title, in new Cartridges contain a 4 character code in ascii
+    pub(crate) new_licensee_code: Option<String>, // 0x0144 - 0x0145 2 character ASCII
+    pub(crate) sgb_flag: bool,                    // 0x0146
+    pub(crate) cartridge_type: u8,                // 0x0147
+    pub(crate) rom_size: u8,                      // 0x0148 32 KiB × (1 << <value>)
+    pub(crate) ram_size: u8,                      // 0x0149
+    pub(crate) destination_code: DestinationCode, //0x014A
+    pub(crate) old_license_code: OldLicenseCode,  // 0x14B
+    pub(crate) mask_rom_version_number: u8,       // 0x014C
+    pub(crate) header_checksum: u8,               // 0x014D
+    pub(crate) global_checksum: u16,              // 0x014E - 0x014F
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapperKind {
+    NoMbc,
+    Mbc1,
+    Mbc2,
+    Mmm01,
+    Mbc3,
+    Mbc5,
+    Mbc6,
+    Mbc7,
+    PocketCamera,
+    Tama5,
+    Huc3,
+    Huc1,
+}
+
+/**
+ * The mapper and the extra hardware on the cartridge, decoded from the cartridge type (0x0147)
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CartridgeType {
+    pub mapper: MapperKind,
+    // External RAM, MBC2 has RAM built into the mapper so it doesn't count
+    pub has_ram: bool,
+    pub has_battery: bool,
+    // Real-time clock
+    pub has_timer: bool,
+    pub has_rumble: bool,
+    // Accelerometer
+    pub has_sensor: bool,
+}
+
+impl CartridgeType {
+    const fn new(mapper: MapperKind) -> Self {
+        CartridgeType {
+            mapper,
+            has_ram: false,
+            has_battery: false,
+            has_timer: false,
+            has_rumble: false,
+            has_sensor: false,
+        }
+    }
+
+    const fn ram(mut self) -> Self {
+        self.has_ram = true;
+        self
+    }
+
+    const fn battery(mut self) -> Self {
+        self.has_battery = true;
+        self
+    }
+
+    const fn timer(mut self) -> Self {
+        self.has_timer = true;
+        self
+    }
+
+    const fn rumble(mut self) -> Self {
+        self.has_rumble = true;
+        self
+    }
+
+    const fn sensor(mut self) -> Self {
+        self.has_sensor = true;
+        self
+    }
+}
+
+impl TryFrom<u8> for CartridgeType {
+    type Error = io::Error;
+
+    fn try_from(code: u8) -> io::Result<Self> {
+        use MapperKind::*;
+
+        let cartridge_type = match code {
+            0x00 => CartridgeType::new(NoMbc),
+            0x01 => CartridgeType::new(Mbc1),
+            0x02 => CartridgeType::new(Mbc1).ram(),
+            0x03 => CartridgeType::new(Mbc1).ram().battery(),
+            0x05 => CartridgeType::new(Mbc2),
+            0x06 => CartridgeType::new(Mbc2).battery(),
+            0x08 => CartridgeType::new(NoMbc).ram(),
+            0x09 => CartridgeType::new(NoMbc).ram().battery(),
+            0x0B => CartridgeType::new(Mmm01),
+            0x0C => CartridgeType::new(Mmm01).ram(),
+            0x0D => CartridgeType::new(Mmm01).ram().battery(),
+            0x0F => CartridgeType::new(Mbc3).timer().battery(),
+            0x10 => CartridgeType::new(Mbc3).timer().ram().battery(),
+            0x11 => CartridgeType::new(Mbc3),
+            0x12 => CartridgeType::new(Mbc3).ram(),
+            0x13 => CartridgeType::new(Mbc3).ram().battery(),
+            0x19 => CartridgeType::new(Mbc5),
+            0x1A => CartridgeType::new(Mbc5).ram(),
+            0x1B => CartridgeType::new(Mbc5).ram().battery(),
+            0x1C => CartridgeType::new(Mbc5).rumble(),
+            0x1D => CartridgeType::new(Mbc5).rumble().ram(),
+            0x1E => CartridgeType::new(Mbc5).rumble().ram().battery(),
+            0x20 => CartridgeType::new(Mbc6).ram().battery(),
+            0x22 => CartridgeType::new(Mbc7).sensor().rumble().ram().battery(),
+            0xFC => CartridgeType::new(PocketCamera).ram().battery(),
+            0xFD => CartridgeType::new(Tama5).timer().ram().battery(),
+            0xFE => CartridgeType::new(Huc3).timer().ram().battery(),
+            0xFF => CartridgeType::new(Huc1).ram().battery(),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Unknown cartridge type {:#04X}", code),
+                ));
+            }
+        };
+
+        Ok(cartridge_type)
+    }
+}
+
+// ROM size (0x0148), 32 KiB × (1 << <value>) apart from a few odd sizes that were never released
+#[derive(Debug, Clone, Copy, PartialEq, Eq, num_enum::TryFromPrimitive)]
+#[repr(u8)]
+pub enum RomSize {
+    Kib32 = 0x00,
+    Kib64 = 0x01,
+    Kib128 = 0x02,
+    Kib256 = 0x03,
+    Kib512 = 0x04,
+    Mib1 = 0x05,
+    Mib2 = 0x06,
+    Mib4 = 0x07,
+    Mib8 = 0x08,
+    Mib1_1 = 0x52,
+    Mib1_2 = 0x53,
+    Mib1_5 = 0x54,
+}
+
+impl RomSize {
+    /**
+     * Get the smallest power of two size that fits a ROM, None if it is too big for any cartridge
+     */
+    pub fn fit(length: usize) -> Option<Self> {
+        (RomSize::Kib32 as u8..=RomSize::Mib8 as u8)
+            .filter_map(|code| RomSize::try_from(code).ok())
+            .find(|size| size.get_size() >= length)
+    }
+
+    /**
+     * Get the ROM size in bytes
+     */
+    pub fn get_size(self) -> usize {
+        self.get_bank_count() * ROM_BANK_SIZE
+    }
+
+    /**
+     * Get the number of 16 KiB banks
+     */
+    pub fn get_bank_count(self) -> usize {
+        match self {
+            RomSize::Mib1_1 => 72,
+            RomSize::Mib1_2 => 80,
+            RomSize::Mib1_5 => 96,
+            _ => 2 << self as u8,
+        }
+    }
 }
 
+// RAM size (0x0149)
 #[derive(Debug, Clone, Copy, PartialEq, Eq, num_enum::TryFromPrimitive)]
 #[repr(u8)]
-enum DestinationCode {
+pub enum RamSize {
+    None = 0x00,
+    // Listed in some unofficial docs as 2 KiB, but no licensed cartridge uses it
+    Unused = 0x01,
+    Kib8 = 0x02,
+    Kib32 = 0x03,
+    Kib128 = 0x04,
+    Kib64 = 0x05,
+}
+
+impl RamSize {
+    /**
+     * Get the RAM size in bytes
+     */
+    pub fn get_size(self) -> usize {
+        match self {
+            RamSize::None | RamSize::Unused => 0,
+            RamSize::Kib8 => 0x2000,
+            RamSize::Kib32 => 0x8000,
+            RamSize::Kib128 => 0x20000,
+            RamSize::Kib64 => 0x10000,
+        }
+    }
+}
+
+// CGB flag (0x0143), older cartridges have the last character of the title here
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum CgbFlag {
+    DmgOnly,
+    // Uses CGB features but still runs on older models
+    Compatible,
+    CgbOnly,
+}
+
+impl From<u8> for CgbFlag {
+    fn from(value: u8) -> Self {
+        // The CGB boot ROM only looks at bit 7, 0xC0 is a promise the game checks the model itself
+        match value {
+            0xC0 => CgbFlag::CgbOnly,
+            _ if value & 0x80 != 0 => CgbFlag::Compatible,
+            _ => CgbFlag::DmgOnly,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, num_enum::TryFromPrimitive)]
+#[repr(u8)]
+pub(crate) enum DestinationCode {
     Japan = 0x00,
     Overseas = 0x01,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, num_enum::TryFromPrimitive)]
 #[repr(u8)]
-enum OldLicenseCode {
+pub(crate) enum OldLicenseCode {
     None = 0x00,
     Nintendo = 0x01,
     Capcom = 0x08,
@@ -253,18 +495,39 @@ static NEW_LICENSE_CODES: LazyLock<HashMap<&'static str, &'static str>> = LazyLo
 
 impl RomHeader {
     pub fn parse(rom: &Vec<u8>) -> io::Result<Self> {
-        let logo = rom[0x0104..(0x0133 + 1)].try_into().unwrap();
+        if rom.len() < HEADER_END {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("A {} byte file is too small to be a ROM", rom.len()),
+            ));
+        }
 
-        let title_bytes = &rom[0x0134..(0x0143 + 1)];
+        let logo = rom[LOGO_START..LOGO_START + NINTENDO_LOGO.len()]
+            .try_into()
+            .unwrap();
+
+        let cgb_flag = CgbFlag::from(rom[CGB_FLAG_ADDR]);
+        // Only cartridges made after the CGB have a manufacturer code, and not all of them
+        let manufacturer_bytes = &rom[MANUFACTURER_CODE_START..CGB_FLAG_ADDR];
+        let manufacturer_code = (cgb_flag != CgbFlag::DmgOnly
+            && manufacturer_bytes
+                .iter()
+                .all(|byte| byte.is_ascii_uppercase() || byte.is_ascii_digit()))
+        .then(|| String::from_utf8_lossy(manufacturer_bytes).to_string());
+
+        let title_end = match (cgb_flag, &manufacturer_code) {
+            (_, Some(_)) => MANUFACTURER_CODE_START,
+            (CgbFlag::DmgOnly, None) => CGB_FLAG_ADDR + 1,
+            (_, None) => CGB_FLAG_ADDR,
+        };
+        let title_bytes = &rom[TITLE_START..title_end];
         let title = String::from_utf8_lossy(title_bytes)
             .trim_matches('\0')
             .to_string();
 
-        let cgb_flag = rom[0x0143] == 0x80 || rom[0x0143] == 0xC0;
-        let manufacturer_code = None;
-
-        let binding = String::from_utf8((&rom[0x0144..(0x0145 + 1)]).to_vec())
-            .expect("Invalid new license code");
+        let binding =
+            String::from_utf8_lossy(&rom[NEW_LICENSEE_CODE_START..NEW_LICENSEE_CODE_START + 2])
+                .to_string();
 
         let new_licensee_code_raw_ascii = binding.as_str();
 
@@ -272,20 +535,21 @@ impl RomHeader {
             .get(new_licensee_code_raw_ascii)
             .map(|&code| code.to_string());
 
-        let sgb_flag = rom[0x0146] == 0x03;
-        let cartridge_type = rom[0x0147];
-        let rom_size = rom[0x0148];
-        let ram_size = rom[0x0149];
+        let sgb_flag = rom[SGB_FLAG_ADDR] == SGB_FLAG;
+        let cartridge_type = rom[CARTRIDGE_TYPE_ADDR];
+        let rom_size = rom[ROM_SIZE_ADDR];
+        let ram_size = rom[RAM_SIZE_ADDR];
 
         let destination_code =
             DestinationCode::try_from(rom[0x014A]).unwrap_or(DestinationCode::Overseas);
 
         let old_license_code =
-            OldLicenseCode::try_from(rom[0x014B]).unwrap_or(OldLicenseCode::None);
+            OldLicenseCode::try_from(rom[OLD_LICENSEE_CODE_ADDR]).unwrap_or(OldLicenseCode::None);
 
         let mask_rom_version_number = rom[0x014C];
-        let header_checksum = rom[0x014D];
-        let global_checksum = u16::from_be_bytes(rom[0x014E..(0x014F + 1)].try_into().unwrap());
+        let header_checksum = rom[HEADER_CHECKSUM_ADDR];
+        let global_checksum =
+            u16::from_be_bytes(rom[GLOBAL_CHECKSUM_ADDR..HEADER_END].try_into().unwrap());
 
         return Ok(Self {
             logo,
@@ -306,24 +570,123 @@ impl RomHeader {
     }
 
     /**
-     * Get RAM size in kilobytes
+     * Get the publisher, newer cartridges store it in the new licensee code (0x0144 - 0x0145)
      */
-    pub fn get_ram_size(self) -> u8 {
-        match self.ram_size {
-            3 => 32,
-            4 => 128,
-            5 => 64,
-            1 => 0,
-            0 => 0,
-            _ => panic!("Unexpected ram size, there was a problem parsing the header."),
+    pub fn get_licensee(&self) -> String {
+        if self.old_license_code != OldLicenseCode::NewLicenseCode {
+            return format!("{:?}", self.old_license_code);
         }
+
+        self.new_licensee_code
+            .clone()
+            .unwrap_or_else(|| "Unknown".to_string())
+    }
+
+    /**
+     * Get the mapper and the hardware on the cartridge (0x0147)
+     */
+    pub fn get_cartridge_type(&self) -> io::Result<CartridgeType> {
+        CartridgeType::try_from(self.cartridge_type)
+    }
+
+    /**
+     * Get the ROM size (0x0148)
+     */
+    pub fn get_rom_size(&self) -> io::Result<RomSize> {
+        RomSize::try_from(self.rom_size).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Unknown ROM size {:#04X}", self.rom_size),
+            )
+        })
+    }
+
+    /**
+     * Get the size of the external RAM on the cartridge (0x0149)
+     */
+    pub fn get_ram_size(&self) -> io::Result<RamSize> {
+        RamSize::try_from(self.ram_size).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Unknown RAM size {:#04X}", self.ram_size),
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_header(cartridge_type: u8, rom_size: u8, ram_size: u8) -> RomHeader {
+        let mut rom = vec![0; 0x8000];
+        rom[CARTRIDGE_TYPE_ADDR] = cartridge_type;
+        rom[ROM_SIZE_ADDR] = rom_size;
+        rom[RAM_SIZE_ADDR] = ram_size;
+        RomHeader::parse(&rom).unwrap()
+    }
+
+    #[test]
+    fn decodes_cartridge_hardware() {
+        let cartridge_type = get_header(0x10, 0x00, 0x00).get_cartridge_type().unwrap();
+        assert_eq!(cartridge_type.mapper, MapperKind::Mbc3);
+        assert!(cartridge_type.has_timer && cartridge_type.has_ram && cartridge_type.has_battery);
+        assert!(!cartridge_type.has_rumble);
+
+        let cartridge_type = get_header(0x22, 0x00, 0x00).get_cartridge_type().unwrap();
+        assert_eq!(cartridge_type.mapper, MapperKind::Mbc7);
+        assert!(cartridge_type.has_sensor && cartridge_type.has_rumble);
+
+        let error = get_header(0x04, 0x00, 0x00)
+            .get_cartridge_type()
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn decodes_rom_size() {
+        assert_eq!(
+            get_header(0, 0x00, 0).get_rom_size().unwrap().get_size(),
+            0x8000
+        );
+        assert_eq!(
+            get_header(0, 0x08, 0).get_rom_size().unwrap().get_size(),
+            0x800000
+        );
+        assert_eq!(
+            get_header(0, 0x52, 0)
+                .get_rom_size()
+                .unwrap()
+                .get_bank_count(),
+            72
+        );
+        assert!(get_header(0, 0x09, 0).get_rom_size().is_err());
+    }
+
+    #[test]
+    fn fits_rom_size() {
+        assert_eq!(RomSize::fit(0x100), Some(RomSize::Kib32));
+        assert_eq!(RomSize::fit(0x8001), Some(RomSize::Kib64));
+        assert_eq!(RomSize::fit(0x800001), None);
     }
 
-    pub fn get_cartridge_type(self) {
-        unimplemented!("Get cartridge type");
+    #[test]
+    fn decodes_ram_size() {
+        assert_eq!(get_header(0, 0, 0x01).get_ram_size().unwrap().get_size(), 0);
+        assert_eq!(
+            get_header(0, 0, 0x04).get_ram_size().unwrap().get_size(),
+            0x20000
+        );
+        assert_eq!(
+            get_header(0, 0, 0x05).get_ram_size().unwrap().get_size(),
+            0x10000
+        );
+        assert!(get_header(0, 0, 0x06).get_ram_size().is_err());
     }
 
-    pub fn get_rom_size(self) {
-        unimplemented!("Get cartridge type");
+    #[test]
+    fn rejects_files_smaller_than_the_header() {
+        let error = RomHeader::parse(&vec![0; HEADER_END - 1]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
     }
 }
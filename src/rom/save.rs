@@ -0,0 +1,115 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// Wait for the game to stop writing for a second before saving, games write saves a byte at a time
+const SAVE_DELAY_FRAMES: usize = 60;
+// Games that keep writing to external RAM still get saved every 10 seconds
+const MAX_SAVE_DELAY_FRAMES: usize = 600;
+
+/**
+ * Battery backed RAM persisted to a .sav file next to the ROM, as a raw dump of the cartridge RAM
+ */
+#[derive(Debug)]
+pub struct SaveFile {
+    path: PathBuf,
+    // What is on disk, used to skip writes when nothing changed
+    last_saved: Vec<u8>,
+    // Frames since the game last wrote to external RAM, None if there is nothing to save
+    frames_since_write: Option<usize>,
+    // Frames since the first write that hasn't been saved yet
+    frames_since_first_write: usize,
+}
+
+impl SaveFile {
+    pub fn new(rom_path: &str) -> Self {
+        SaveFile {
+            path: Path::new(rom_path).with_extension("sav"),
+            last_saved: Vec::new(),
+            frames_since_write: None,
+            frames_since_first_write: 0,
+        }
+    }
+
+    /**
+     * Read the save from disk, a missing save file is not an error since the game hasn't saved yet
+     */
+    pub fn load(&mut self) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(&self.path) {
+            Ok(data) => {
+                self.last_saved = data.clone();
+                Ok(Some(data))
+            }
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    pub fn save(&mut self, data: &[u8]) -> io::Result<()> {
+        self.frames_since_write = None;
+        self.frames_since_first_write = 0;
+        if data.is_empty() || data == self.last_saved {
+            return Ok(());
+        }
+
+        // Write to a temporary file first so a crash mid-write doesn't corrupt the save
+        let temp_path = self.path.with_extension("sav.tmp");
+        fs::write(&temp_path, data)?;
+        fs::rename(&temp_path, &self.path)?;
+        self.last_saved = data.to_vec();
+        return Ok(());
+    }
+
+    /**
+     * Called once per frame, returns true when the game has stopped writing for long enough that
+     * it should be saved
+     */
+    pub fn update(&mut self, was_written: bool) -> bool {
+        let frames_since_write = if was_written {
+            0
+        } else {
+            match self.frames_since_write {
+                Some(frames) => frames + 1,
+                None => return false,
+            }
+        };
+
+        self.frames_since_write = Some(frames_since_write);
+        self.frames_since_first_write += 1;
+
+        return frames_since_write >= SAVE_DELAY_FRAMES
+            || self.frames_since_first_write >= MAX_SAVE_DELAY_FRAMES;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn waits_for_writes_to_stop() {
+        let mut save_file = SaveFile::new("game.gb");
+        assert!(!save_file.update(false));
+
+        assert!(!save_file.update(true));
+        for _ in 1..SAVE_DELAY_FRAMES {
+            assert!(!save_file.update(false));
+        }
+        assert!(save_file.update(false));
+    }
+
+    #[test]
+    fn saves_games_that_keep_writing() {
+        let mut save_file = SaveFile::new("game.gb");
+        for _ in 1..MAX_SAVE_DELAY_FRAMES {
+            assert!(!save_file.update(true));
+        }
+        assert!(save_file.update(true));
+    }
+
+    #[test]
+    fn saves_next_to_rom() {
+        let save_file = SaveFile::new("roms/game.gb");
+        assert_eq!(save_file.path, Path::new("roms/game.sav"));
+    }
+}
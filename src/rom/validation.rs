@@ -0,0 +1,237 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::rom::rom_header::{
+    CgbFlag, GLOBAL_CHECKSUM_ADDR, HEADER_CHECKSUM_ADDR, HEADER_CHECKSUM_START, NINTENDO_LOGO,
+    OldLicenseCode, RomHeader,
+};
+
+/**
+ * What to do with a ROM the boot ROM would refuse to start, real hardware hangs when the logo or
+ * the header checksum is wrong
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockUpPolicy {
+    // Run the game anyway, emulators usually skip the boot ROM checks
+    Ignore,
+    // Fail to load the ROM
+    Refuse,
+    // Hang like the boot ROM does
+    Emulate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Checksum<T> {
+    // Stored in the header
+    pub expected: T,
+    // Computed from the ROM
+    pub actual: T,
+}
+
+impl<T: PartialEq> Checksum<T> {
+    pub fn is_valid(&self) -> bool {
+        self.expected == self.actual
+    }
+}
+
+/**
+ * Result of checking a ROM against its header, nothing here stops the ROM from being loaded
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ValidationReport {
+    pub is_logo_valid: bool,
+    pub header_checksum: Checksum<u8>,
+    // Not checked by the hardware, but a mismatch usually means a bad dump or a broken build
+    pub global_checksum: Checksum<u16>,
+    // Size from the header (0x0148), None when the size code is unknown
+    pub declared_size: Option<usize>,
+    pub actual_size: usize,
+    pub cgb_flag: CgbFlag,
+    pub has_sgb_flag: bool,
+    // The SGB only enables its features if the old licensee code is 0x33
+    pub is_sgb_supported: bool,
+}
+
+impl ValidationReport {
+    pub fn new(rom: &[u8], header: &RomHeader) -> Self {
+        let declared_size = header.get_rom_size().ok().map(|size| size.get_size());
+
+        ValidationReport {
+            is_logo_valid: header.logo == NINTENDO_LOGO,
+            header_checksum: Checksum {
+                expected: header.header_checksum,
+                actual: compute_header_checksum(rom),
+            },
+            global_checksum: Checksum {
+                expected: header.global_checksum,
+                actual: compute_global_checksum(rom),
+            },
+            declared_size,
+            actual_size: rom.len(),
+            cgb_flag: header.cgb_flag,
+            has_sgb_flag: header.sgb_flag,
+            is_sgb_supported: header.sgb_flag
+                && header.old_license_code == OldLicenseCode::NewLicenseCode,
+        }
+    }
+
+    pub fn is_size_valid(&self) -> bool {
+        self.declared_size == Some(self.actual_size)
+    }
+
+    /**
+     * The boot ROM hangs instead of starting the game
+     */
+    pub fn would_lock_up(&self) -> bool {
+        !self.is_logo_valid || !self.header_checksum.is_valid()
+    }
+
+    pub fn is_valid(&self) -> bool {
+        !self.would_lock_up()
+            && self.global_checksum.is_valid()
+            && self.is_size_valid()
+            && self.has_sgb_flag == self.is_sgb_supported
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let status = |is_valid: bool| if is_valid { "OK" } else { "FAIL" };
+
+        writeln!(f, "Nintendo logo:   {}", status(self.is_logo_valid))?;
+        writeln!(
+            f,
+            "Header checksum: {} (header {:#04X}, computed {:#04X})",
+            status(self.header_checksum.is_valid()),
+            self.header_checksum.expected,
+            self.header_checksum.actual
+        )?;
+        writeln!(
+            f,
+            "Global checksum: {} (header {:#06X}, computed {:#06X})",
+            status(self.global_checksum.is_valid()),
+            self.global_checksum.expected,
+            self.global_checksum.actual
+        )?;
+
+        let declared_size = match self.declared_size {
+            Some(size) => format!("{} bytes", size),
+            None => "unknown".to_string(),
+        };
+        writeln!(
+            f,
+            "ROM size:        {} (header {}, file {} bytes)",
+            status(self.is_size_valid()),
+            declared_size,
+            self.actual_size
+        )?;
+
+        writeln!(f, "CGB flag:        {:?}", self.cgb_flag)?;
+        write!(f, "SGB flag:        ")?;
+        match (self.has_sgb_flag, self.is_sgb_supported) {
+            (false, _) => write!(f, "not set"),
+            (true, true) => write!(f, "set"),
+            (true, false) => write!(f, "FAIL (ignored, old licensee code is not 0x33)"),
+        }
+    }
+}
+
+/**
+ * Checksum of 0x0134 - 0x014C that the boot ROM verifies
+ */
+pub(crate) fn compute_header_checksum(rom: &[u8]) -> u8 {
+    rom[HEADER_CHECKSUM_START..HEADER_CHECKSUM_ADDR]
+        .iter()
+        .fold(0u8, |checksum, byte| {
+            checksum.wrapping_sub(*byte).wrapping_sub(1)
+        })
+}
+
+/**
+ * Sum of every byte in the ROM apart from the global checksum itself
+ */
+pub(crate) fn compute_global_checksum(rom: &[u8]) -> u16 {
+    rom.iter()
+        .enumerate()
+        .filter(|(i, _)| *i != GLOBAL_CHECKSUM_ADDR && *i != GLOBAL_CHECKSUM_ADDR + 1)
+        .fold(0u16, |checksum, (_, byte)| {
+            checksum.wrapping_add(*byte as u16)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rom::rom_header::{LOGO_START, OLD_LICENSEE_CODE_ADDR, SGB_FLAG, SGB_FLAG_ADDR};
+
+    // A 32KiB ROM with the logo and both checksums set
+    fn get_valid_rom() -> Vec<u8> {
+        let mut rom = vec![0; 0x8000];
+        rom[LOGO_START..LOGO_START + NINTENDO_LOGO.len()].copy_from_slice(&NINTENDO_LOGO);
+        fix_checksums(&mut rom);
+        rom
+    }
+
+    fn fix_checksums(rom: &mut [u8]) {
+        rom[HEADER_CHECKSUM_ADDR] = compute_header_checksum(rom);
+        let global_checksum = compute_global_checksum(rom).to_be_bytes();
+        rom[GLOBAL_CHECKSUM_ADDR..GLOBAL_CHECKSUM_ADDR + 2].copy_from_slice(&global_checksum);
+    }
+
+    fn validate(rom: &Vec<u8>) -> ValidationReport {
+        ValidationReport::new(rom, &RomHeader::parse(rom).unwrap())
+    }
+
+    #[test]
+    fn header_checksum_matches_the_boot_rom() {
+        // 25 zero bytes, each subtracts 1
+        assert_eq!(compute_header_checksum(&vec![0; 0x8000]), 0xE7);
+    }
+
+    #[test]
+    fn valid_rom_passes() {
+        let report = validate(&get_valid_rom());
+        assert!(report.is_valid());
+        assert!(!report.would_lock_up());
+    }
+
+    #[test]
+    fn bad_logo_or_header_checksum_locks_up() {
+        let mut rom = get_valid_rom();
+        rom[LOGO_START] = 0x00;
+        fix_checksums(&mut rom);
+        assert!(validate(&rom).would_lock_up());
+
+        let mut rom = get_valid_rom();
+        rom[HEADER_CHECKSUM_ADDR] ^= 0xFF;
+        let report = validate(&rom);
+        assert!(report.would_lock_up());
+        assert!(!report.header_checksum.is_valid());
+    }
+
+    #[test]
+    fn size_and_global_checksum_do_not_lock_up() {
+        let mut rom = get_valid_rom();
+        rom.extend([0x01; 0x100]);
+        let report = validate(&rom);
+        assert!(!report.would_lock_up());
+        assert!(!report.is_size_valid());
+        assert!(!report.global_checksum.is_valid());
+        assert!(!report.is_valid());
+    }
+
+    #[test]
+    fn sgb_flag_needs_new_licensee_code() {
+        let mut rom = get_valid_rom();
+        rom[SGB_FLAG_ADDR] = SGB_FLAG;
+        fix_checksums(&mut rom);
+        let report = validate(&rom);
+        assert!(report.has_sgb_flag && !report.is_sgb_supported);
+        assert!(!report.is_valid());
+
+        rom[OLD_LICENSEE_CODE_ADDR] = 0x33;
+        fix_checksums(&mut rom);
+        assert!(validate(&rom).is_valid());
+    }
+}
@@ -1,10 +1,11 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use pixels::{Pixels, SurfaceTexture};
 use winit::dpi::LogicalSize;
 use winit::event::WindowEvent;
 use winit::event_loop::{ActiveEventLoop, EventLoop};
-use winit::keyboard::Key;
+use winit::keyboard::{Key, NamedKey};
 use winit::window::Window;
 use winit::{application::ApplicationHandler, event_loop::ControlFlow};
 
@@ -20,8 +21,14 @@ pub struct UI<'a> {
 #[derive(Debug)]
 struct App<'a> {
     context: Context,
+    // Window title, the name of the game
+    title: String,
     window: Option<Arc<Window>>,
     pixels: Option<Pixels<'a>>,
+    // Arrow keys held down, used to tilt cartridges with an accelerometer
+    tilt_keys: [bool; 4],
+    // Button pressed by each keyboard key
+    key_bindings: HashMap<String, Button>,
 }
 
 impl<'a> ApplicationHandler for App<'a> {
@@ -31,6 +38,7 @@ impl<'a> ApplicationHandler for App<'a> {
         let window = event_loop
             .create_window(
                 Window::default_attributes()
+                    .with_title(&self.title)
                     .with_inner_size(size)
                     .with_min_inner_size(size),
             )
@@ -87,11 +95,17 @@ impl<'a> ApplicationHandler for App<'a> {
                 //     _ => {}
                 // },
                 Key::Character(str) => {
-                    let button = key_to_button(&str);
-                    if let Some(button) = button {
+                    let button = self.key_bindings.get(str.as_str());
+                    if let Some(&button) = button {
                         self.context.press_button(button, event.state.is_pressed());
                     }
                 }
+                Key::Named(named_key) => {
+                    if let Some(index) = key_to_tilt_index(named_key) {
+                        self.tilt_keys[index] = event.state.is_pressed();
+                        self.update_tilt();
+                    }
+                }
                 _ => {}
             },
 
@@ -104,29 +118,34 @@ impl<'a> ApplicationHandler for App<'a> {
     }
 }
 
-fn key_to_button(key: &str) -> Option<Button> {
+// Index into the held tilt keys: up, down, left, right
+fn key_to_tilt_index(key: NamedKey) -> Option<usize> {
     match key {
-        "w" => Some(Button::Up),
-        "a" => Some(Button::Left),
-        "s" => Some(Button::Down),
-        "d" => Some(Button::Right),
-        "j" => Some(Button::A),
-        "k" => Some(Button::B),
-        "u" => Some(Button::Select),
-        "i" => Some(Button::Start),
+        NamedKey::ArrowUp => Some(0),
+        NamedKey::ArrowDown => Some(1),
+        NamedKey::ArrowLeft => Some(2),
+        NamedKey::ArrowRight => Some(3),
         _ => None,
     }
 }
 
 impl<'a> App<'a> {
-    pub fn new(context: Context) -> Self {
+    pub fn new(context: Context, title: &str, key_bindings: HashMap<String, Button>) -> Self {
         App {
             context: context,
+            title: title.to_string(),
             window: None,
             pixels: None,
+            tilt_keys: [false; 4],
+            key_bindings,
         }
     }
 
+    fn update_tilt(&mut self) {
+        let [up, down, left, right] = self.tilt_keys.map(|is_held| is_held as i8 as f32);
+        self.context.set_tilt(right - left, down - up);
+    }
+
     fn get_next_frame(&mut self) {
         let buffer = self.context.step();
         if let Some(buffer) = buffer {
@@ -144,9 +163,12 @@ impl<'a> App<'a> {
 }
 
 impl<'a> UI<'a> {
-    pub fn new(context: Context) -> Self {
+    /**
+     * Key bindings map each keyboard key to the button it presses, see GameConfig::get_key_bindings
+     */
+    pub fn new(context: Context, title: &str, key_bindings: HashMap<String, Button>) -> Self {
         UI {
-            app: App::new(context),
+            app: App::new(context, title, key_bindings),
         }
     }
 